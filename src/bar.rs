@@ -0,0 +1,278 @@
+use serde::Serialize;
+
+use crate::bluetooth::BluetoothDevice;
+use crate::config::Config;
+use crate::privacy;
+
+/// How each bar line is written.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Output {
+    /// Plain text, for Polybar (`tail = true`) and i3blocks (`interval=persist`).
+    #[default]
+    Plain,
+    /// One JSON object per line, for a Waybar custom module with `"return-type": "json"`.
+    Waybar,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Options {
+    pub output: Output,
+    /// Print one line and exit, for bars that poll.
+    pub once: bool,
+    /// Replaces `bar_format` from the config.
+    pub format: Option<String>,
+}
+
+/// Adapter state as the bar shows it; `{status}` expands to the lowercase name.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum State {
+    On,
+    Off,
+    /// Blocked by rfkill, e.g. airplane mode or a hardware switch.
+    Blocked,
+    /// bluetoothd isn't reachable; carries the reason.
+    Unavailable(String),
+}
+
+impl State {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::On => "on",
+            Self::Off => "off",
+            Self::Blocked => "blocked",
+            Self::Unavailable(_) => "unavailable",
+        }
+    }
+}
+
+/// Everything a bar line is rendered from.
+pub struct Snapshot {
+    pub state: State,
+    pub adapter: Option<String>,
+    /// Known devices in the widget's order, so pinned devices come first.
+    pub devices: Vec<BluetoothDevice>,
+}
+
+impl Snapshot {
+    /// What the bar shows while bluetoothd can't be reached.
+    pub fn unavailable(reason: String) -> Self {
+        Self {
+            state: State::Unavailable(reason),
+            adapter: None,
+            devices: Vec::new(),
+        }
+    }
+
+    fn connected(&self) -> impl Iterator<Item = &BluetoothDevice> {
+        self.devices.iter().filter(|device| device.connected)
+    }
+
+    /// The device `{name}` and `{battery}` describe: the first connected
+    /// one, which is a pinned device whenever one is connected.
+    fn primary(&self) -> Option<&BluetoothDevice> {
+        self.connected().next()
+    }
+}
+
+#[derive(Serialize)]
+struct Waybar {
+    text: String,
+    tooltip: String,
+    /// Picks from `format-icons`: `connected` or the state name.
+    alt: &'static str,
+    class: Vec<&'static str>,
+    /// Battery of the primary device, for `format-icons` lists.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    percentage: Option<u8>,
+}
+
+/// Renders one bar line.
+pub fn render(snapshot: &Snapshot, config: &Config, options: &Options) -> String {
+    let waybar = options.output == Output::Waybar;
+    let primary = snapshot.primary();
+    let template = match (&snapshot.state, primary) {
+        (State::On, Some(_)) => options.format.as_deref().unwrap_or(&config.bar_format),
+        (State::On, None) => &config.bar_format_disconnected,
+        _ => &config.bar_format_off,
+    };
+    let text = expand(template, |key| value(key, snapshot, primary, waybar));
+    if !waybar {
+        // Bars read line by line, so a stray newline would split the output.
+        return text.replace('\n', " ");
+    }
+
+    let tooltip = match (&snapshot.state, primary) {
+        (State::On, Some(_)) => snapshot
+            .connected()
+            .map(|device| expand(&config.bar_tooltip_format, |key| value(key, snapshot, Some(device), true)))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        (State::On, None) => "No devices connected".to_string(),
+        (State::Off, _) => "Bluetooth is off".to_string(),
+        (State::Blocked, _) => "Bluetooth is blocked".to_string(),
+        (State::Unavailable(reason), _) => {
+            format!("Bluetooth is unavailable: {}", glib::markup_escape_text(&privacy::mask_text(reason)))
+        }
+    };
+    let alt = if primary.is_some() { "connected" } else { snapshot.state.name() };
+    let mut class = vec![snapshot.state.name()];
+    if primary.is_some() {
+        class.push("connected");
+    }
+
+    serde_json::to_string(&Waybar {
+        text,
+        tooltip,
+        alt,
+        class,
+        percentage: primary.and_then(|device| device.battery),
+    })
+    .expect("bar output always serializes")
+}
+
+/// Value of a placeholder for `device`, or `None` if `key` isn't one.
+/// Waybar reads Pango markup, so values are escaped for it.
+fn value(key: &str, snapshot: &Snapshot, device: Option<&BluetoothDevice>, markup: bool) -> Option<String> {
+    let value = match key {
+        "status" => snapshot.state.name().to_string(),
+        "connected_count" => snapshot.connected().count().to_string(),
+        "adapter" => snapshot.adapter.clone().unwrap_or_default(),
+        "name" => device.map(|device| privacy::mask_name(&device.name, &device.address)).unwrap_or_default(),
+        "address" => device.map(|device| privacy::mask_address(&device.address)).unwrap_or_default(),
+        "battery" => device
+            .and_then(|device| device.battery)
+            .map(|level| format!("{}%", level))
+            .unwrap_or_default(),
+        _ => return None,
+    };
+    Some(if markup { glib::markup_escape_text(&value).to_string() } else { value })
+}
+
+/// Replaces each `{key}` in `template`. Unknown keys are left as written so
+/// typos show up in the bar, and the result is trimmed so an empty
+/// `{battery}` at the end leaves no trailing space.
+fn expand(template: &str, value: impl Fn(&str) -> Option<String>) -> String {
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find('}') else { break };
+        match value(&rest[1..end]) {
+            Some(value) => expanded.push_str(&value),
+            None => expanded.push_str(&rest[..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    expanded.push_str(rest);
+    expanded.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connected(address: &str, name: &str) -> BluetoothDevice {
+        BluetoothDevice {
+            connected: true,
+            ..BluetoothDevice::test(address, name)
+        }
+    }
+
+    fn snapshot(state: State, devices: Vec<BluetoothDevice>) -> Snapshot {
+        Snapshot {
+            state,
+            adapter: Some("hci0".to_string()),
+            devices,
+        }
+    }
+
+    fn plain(format: &str) -> Options {
+        Options {
+            format: Some(format.to_string()),
+            ..Options::default()
+        }
+    }
+
+    fn waybar() -> Options {
+        Options {
+            output: Output::Waybar,
+            ..Options::default()
+        }
+    }
+
+    #[test]
+    fn expands_placeholders_for_the_first_connected_device() {
+        let snapshot = snapshot(
+            State::On,
+            vec![
+                BluetoothDevice {
+                    battery: Some(15),
+                    ..BluetoothDevice::test("00:1A:7D:DA:71:02", "Keyboard")
+                },
+                BluetoothDevice {
+                    battery: Some(80),
+                    ..connected("00:1A:7D:DA:71:01", "Headphones")
+                },
+                connected("00:1A:7D:DA:71:03", "Mouse"),
+            ],
+        );
+        let options = plain("{connected_count} {name} {battery} {status} {adapter}");
+        assert_eq!(render(&snapshot, &Config::default(), &options), "2 Headphones 80% on hci0");
+    }
+
+    #[test]
+    fn trims_an_empty_battery() {
+        let snapshot = snapshot(State::On, vec![connected("00:1A:7D:DA:71:03", "Mouse")]);
+        assert_eq!(render(&snapshot, &Config::default(), &Options::default()), "Mouse");
+    }
+
+    #[test]
+    fn leaves_unknown_placeholders() {
+        let snapshot = snapshot(State::On, vec![connected("00:1A:7D:DA:71:01", "Headphones")]);
+        assert_eq!(render(&snapshot, &Config::default(), &plain("{name} {volume} {")), "Headphones {volume} {");
+    }
+
+    #[test]
+    fn uses_the_disconnected_and_off_formats() {
+        let config = Config::default();
+        let idle = snapshot(State::On, vec![BluetoothDevice::test("00:1A:7D:DA:71:02", "Keyboard")]);
+        assert_eq!(render(&idle, &config, &plain("{name}")), config.bar_format_disconnected);
+        let options = Options::default();
+        assert_eq!(render(&snapshot(State::On, Vec::new()), &config, &options), config.bar_format_disconnected);
+        assert_eq!(render(&snapshot(State::Off, Vec::new()), &config, &options), config.bar_format_off);
+        assert_eq!(render(&snapshot(State::Blocked, Vec::new()), &config, &options), config.bar_format_off);
+    }
+
+    #[test]
+    fn keeps_plain_output_on_one_line() {
+        let snapshot = snapshot(State::On, vec![connected("00:1A:7D:DA:71:01", "Head\nphones")]);
+        assert_eq!(render(&snapshot, &Config::default(), &Options::default()), "Head phones");
+    }
+
+    #[test]
+    fn escapes_markup_for_waybar() {
+        let device = BluetoothDevice {
+            battery: Some(80),
+            ..connected("00:1A:7D:DA:71:01", "Tom & Jerry <3>")
+        };
+        let snapshot = snapshot(State::On, vec![device]);
+        let line = render(&snapshot, &Config::default(), &waybar());
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["text"], "Tom &amp; Jerry &lt;3&gt; 80%");
+        assert_eq!(json["tooltip"], "Tom &amp; Jerry &lt;3&gt; 80%");
+        assert_eq!(json["alt"], "connected");
+        assert_eq!(json["class"], serde_json::json!(["on", "connected"]));
+        assert_eq!(json["percentage"], 80);
+    }
+
+    #[test]
+    fn describes_unavailable_bluetooth_for_waybar() {
+        let line = render(&Snapshot::unavailable("bluetoothd <gone>".to_string()), &Config::default(), &waybar());
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["alt"], "unavailable");
+        assert_eq!(json["class"], serde_json::json!(["unavailable"]));
+        assert_eq!(json["tooltip"], "Bluetooth is unavailable: bluetoothd &lt;gone&gt;");
+        assert!(json.get("percentage").is_none());
+    }
+}
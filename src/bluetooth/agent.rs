@@ -0,0 +1,134 @@
+use bluer::agent::{ReqError, ReqResult};
+use bluer::Address;
+use futures::channel::{mpsc, oneshot};
+use futures::stream::{BoxStream, StreamExt};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How long the user has to answer a pairing prompt before it is cancelled.
+pub const PAIRING_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A pairing question or notice that needs the user's attention.
+///
+/// Every variant carries `closed`, which resolves once the request is over
+/// (answered, timed out or cancelled by the remote device), so the UI knows
+/// when to take its dialog down.
+pub enum PairingRequest {
+    /// Enter the PIN code for a legacy device.
+    PinCode {
+        device: Address,
+        reply: PairingReply<String>,
+        closed: oneshot::Receiver<()>,
+    },
+    /// Enter a numeric passkey.
+    Passkey {
+        device: Address,
+        reply: PairingReply<u32>,
+        closed: oneshot::Receiver<()>,
+    },
+    /// Show a passkey to type on the remote keyboard; `entered` counts the
+    /// digits typed so far and is re-sent as the user types.
+    DisplayPasskey {
+        device: Address,
+        passkey: u32,
+        entered: u16,
+        closed: oneshot::Receiver<()>,
+    },
+    /// Confirm that the remote device shows the same passkey.
+    Confirmation {
+        device: Address,
+        passkey: u32,
+        reply: PairingReply<()>,
+        closed: oneshot::Receiver<()>,
+    },
+    /// Allow the device to use a service, e.g. audio or file transfer.
+    AuthorizeService {
+        device: Address,
+        service: String,
+        reply: PairingReply<()>,
+        closed: oneshot::Receiver<()>,
+    },
+}
+
+/// The user's answer to a pairing request. Dropping it rejects the request.
+pub struct PairingReply<T>(oneshot::Sender<T>);
+
+impl<T> PairingReply<T> {
+    pub fn accept(self, value: T) {
+        let _ = self.0.send(value);
+    }
+
+    pub fn reject(self) {}
+}
+
+/// Routes agent callbacks from a backend to whoever is listening (the window).
+///
+/// Requests arriving while nobody listens are rejected straight away, so
+/// pairing fails fast instead of hanging until bluetoothd times out.
+#[derive(Default)]
+pub struct PairingAgent {
+    listener: Mutex<Option<mpsc::UnboundedSender<PairingRequest>>>,
+}
+
+impl PairingAgent {
+    /// Starts delivering requests to the returned stream, replacing any
+    /// previous listener.
+    pub fn listen(&self) -> BoxStream<'static, PairingRequest> {
+        let (tx, rx) = mpsc::unbounded();
+        if let Ok(mut listener) = self.listener.lock() {
+            *listener = Some(tx);
+        }
+        rx.boxed()
+    }
+
+    fn send(&self, request: PairingRequest) -> ReqResult<()> {
+        let listener = self.listener.lock().map_err(|_| ReqError::Rejected)?;
+        match listener.as_ref() {
+            Some(tx) => tx.unbounded_send(request).map_err(|_| ReqError::Rejected),
+            None => Err(ReqError::Rejected),
+        }
+    }
+
+    /// Sends a request built by `make` and waits for the answer. The `closed`
+    /// guard is dropped on return, or when bluetoothd cancels and drops this
+    /// future.
+    async fn ask<T>(
+        &self,
+        make: impl FnOnce(PairingReply<T>, oneshot::Receiver<()>) -> PairingRequest,
+    ) -> ReqResult<T> {
+        let (reply, answer) = oneshot::channel();
+        let (_closed_guard, closed) = oneshot::channel::<()>();
+        self.send(make(PairingReply(reply), closed))?;
+
+        match tokio::time::timeout(PAIRING_TIMEOUT, answer).await {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(_)) => Err(ReqError::Rejected),
+            Err(_) => Err(ReqError::Canceled),
+        }
+    }
+
+    pub async fn request_pin_code(&self, device: Address) -> ReqResult<String> {
+        self.ask(|reply, closed| PairingRequest::PinCode { device, reply, closed }).await
+    }
+
+    pub async fn request_passkey(&self, device: Address) -> ReqResult<u32> {
+        self.ask(|reply, closed| PairingRequest::Passkey { device, reply, closed }).await
+    }
+
+    pub async fn request_confirmation(&self, device: Address, passkey: u32) -> ReqResult<()> {
+        self.ask(|reply, closed| PairingRequest::Confirmation { device, passkey, reply, closed })
+            .await
+    }
+
+    pub async fn authorize_service(&self, device: Address, service: String) -> ReqResult<()> {
+        self.ask(|reply, closed| PairingRequest::AuthorizeService { device, service, reply, closed })
+            .await
+    }
+
+    /// Shows `passkey` until the returned guard is dropped.
+    pub fn display_passkey(&self, device: Address, passkey: u32, entered: u16) -> ReqResult<oneshot::Sender<()>> {
+        let (guard, closed) = oneshot::channel();
+        self.send(PairingRequest::DisplayPasskey { device, passkey, entered, closed })?;
+        Ok(guard)
+    }
+}
@@ -0,0 +1,438 @@
+use bluer::agent::{Agent, AgentHandle};
+use bluer::{Adapter, AdapterEvent, AdapterProperty, Address, Device, Session, SessionEvent};
+use futures::future::{BoxFuture, FutureExt};
+use futures::stream::{self, BoxStream, SelectAll, StreamExt};
+use std::sync::{Arc, Mutex};
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::privacy;
+
+use super::agent::{PairingAgent, PairingRequest};
+use super::bus::SystemBus;
+use super::{channel_stream, AdapterInfo, BluetoothBackend, BluetoothDevice, BluetoothEvent, DeviceDetails};
+
+/// Backend that talks to bluetoothd over D-Bus.
+pub struct BluezBackend {
+    session: Session,
+    /// For `Bonded`, which bluer doesn't expose.
+    bus: SystemBus,
+    /// The selected adapter; cleared by a subscription when it is unplugged.
+    adapter: Arc<Mutex<Option<Adapter>>>,
+    pairing: Arc<PairingAgent>,
+    #[allow(dead_code)] // Dropping the handle unregisters the agent
+    agent: Option<AgentHandle>,
+}
+
+impl BluezBackend {
+    /// Uses the adapter named `preferred` if it is present, otherwise the
+    /// default adapter, or none until one is plugged in. Fails only if
+    /// bluetoothd can't be reached. Must be awaited inside a tokio runtime;
+    /// bluer spawns its D-Bus tasks on it.
+    ///
+    /// Registers the pairing agent if `agent` is set; without it pairing
+    /// prompts go to whatever agent is registered system-wide.
+    pub async fn new(preferred: Option<String>, agent: bool) -> Result<Self> {
+        let session = Session::new().await.context("Cannot connect to the system D-Bus")?;
+        let bus = SystemBus::new().context("Cannot connect to the system D-Bus")?;
+        let names = session.adapter_names().await.context("bluetoothd is not running")?;
+        let adapter = match preferred.filter(|name| names.contains(name)) {
+            Some(name) => Some(session.adapter(&name)?),
+            None => session.default_adapter().await.ok(),
+        };
+        // Power is left as it is; the window applies the configured power policy.
+
+        let pairing = Arc::new(PairingAgent::default());
+        // Pairing still works through the system default agent if ours is refused.
+        let agent = match agent {
+            true => match session.register_agent(bluez_agent(pairing.clone())).await {
+                Ok(handle) => Some(handle),
+                Err(e) => {
+                    eprintln!("Failed to register pairing agent: {}", e);
+                    None
+                }
+            },
+            false => None,
+        };
+
+        Ok(Self {
+            session,
+            bus,
+            adapter: Arc::new(Mutex::new(adapter)),
+            pairing,
+            agent,
+        })
+    }
+
+    fn selected(&self) -> Result<Option<Adapter>> {
+        Ok(self.adapter.lock().map_err(|_| anyhow!("Adapter state poisoned"))?.clone())
+    }
+
+    fn adapter(&self) -> Result<Adapter> {
+        self.selected()?.ok_or_else(|| anyhow!("No Bluetooth adapter"))
+    }
+}
+
+/// Bridges bluer's agent callbacks to `pairing`. Registered as a per-application
+/// agent, so BlueZ asks us about pairings started from the widget and leaves
+/// the desktop's default agent alone.
+fn bluez_agent(pairing: Arc<PairingAgent>) -> Agent {
+    let pin_code = pairing.clone();
+    let passkey = pairing.clone();
+    let display = pairing.clone();
+    let confirmation = pairing.clone();
+    let authorize = pairing;
+
+    Agent {
+        request_default: false,
+        request_pin_code: Some(Box::new(move |req| {
+            let pairing = pin_code.clone();
+            async move { pairing.request_pin_code(req.device).await }.boxed()
+        })),
+        request_passkey: Some(Box::new(move |req| {
+            let pairing = passkey.clone();
+            async move { pairing.request_passkey(req.device).await }.boxed()
+        })),
+        display_passkey: Some(Box::new(move |req| {
+            let pairing = display.clone();
+            async move {
+                let guard = pairing.display_passkey(req.device, req.passkey, req.entered)?;
+                // Keep the prompt up until bluetoothd says the passkey is no longer needed.
+                tokio::spawn(async move {
+                    let _ = req.cancel.await;
+                    drop(guard);
+                });
+                Ok(())
+            }
+            .boxed()
+        })),
+        request_confirmation: Some(Box::new(move |req| {
+            let pairing = confirmation.clone();
+            async move { pairing.request_confirmation(req.device, req.passkey).await }.boxed()
+        })),
+        authorize_service: Some(Box::new(move |req| {
+            let pairing = authorize.clone();
+            async move { pairing.authorize_service(req.device, req.service.to_string()).await }.boxed()
+        })),
+        ..Default::default()
+    }
+}
+
+async fn read_adapter(adapter: &Adapter) -> AdapterInfo {
+    let name = adapter.name().to_string();
+    let alias = adapter.alias().await.unwrap_or_else(|_| name.clone());
+    let address = adapter.address().await.unwrap_or(Address::any());
+
+    AdapterInfo { name, alias, address }
+}
+
+async fn read_device(device: &Device) -> BluetoothDevice {
+    let remote_name = device.name().await.unwrap_or(None);
+    // BlueZ falls back to the remote name while no alias is set
+    let name = match device.alias().await {
+        Ok(alias) => alias,
+        Err(_) => remote_name.clone().unwrap_or_else(|| "Unknown Device".to_string()),
+    };
+    let icon = device.icon().await.unwrap_or(None).unwrap_or_else(|| "bluetooth".to_string());
+    let connected = device.is_connected().await.unwrap_or(false);
+    let paired = device.is_paired().await.unwrap_or(false);
+    let trusted = device.is_trusted().await.unwrap_or(false);
+    let blocked = device.is_blocked().await.unwrap_or(false);
+    let battery = device.battery_percentage().await.unwrap_or(None);
+
+    BluetoothDevice {
+        address: device.address(),
+        name,
+        remote_name,
+        icon,
+        connected,
+        paired,
+        trusted,
+        blocked,
+        battery,
+    }
+}
+
+impl BluetoothBackend for BluezBackend {
+    fn adapters(&self) -> BoxFuture<'_, Result<Vec<AdapterInfo>>> {
+        async move {
+            let mut names = self.session.adapter_names().await?;
+            names.sort();
+            let mut adapters = Vec::new();
+            for name in names {
+                if let Ok(adapter) = self.session.adapter(&name) {
+                    adapters.push(read_adapter(&adapter).await);
+                }
+            }
+            Ok(adapters)
+        }
+        .boxed()
+    }
+
+    fn current_adapter(&self) -> BoxFuture<'_, Result<Option<String>>> {
+        async move { Ok(self.selected()?.map(|adapter| adapter.name().to_string())) }.boxed()
+    }
+
+    fn select_adapter(&self, name: String) -> BoxFuture<'_, Result<()>> {
+        async move {
+            if !self.session.adapter_names().await?.contains(&name) {
+                bail!("Adapter {} is not present", name);
+            }
+            let adapter = self.session.adapter(&name)?;
+            *self.adapter.lock().map_err(|_| anyhow!("Adapter state poisoned"))? = Some(adapter);
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn is_powered(&self) -> BoxFuture<'_, Result<bool>> {
+        async move { Ok(self.adapter()?.is_powered().await?) }.boxed()
+    }
+
+    fn set_powered(&self, powered: bool) -> BoxFuture<'_, Result<()>> {
+        async move {
+            self.adapter()?.set_powered(powered).await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn get_devices(&self) -> BoxFuture<'_, Result<Vec<BluetoothDevice>>> {
+        async move {
+            let adapter = self.adapter()?;
+            let mut devices = Vec::new();
+            for addr in adapter.device_addresses().await? {
+                if let Ok(device) = adapter.device(addr) {
+                    devices.push(read_device(&device).await);
+                }
+            }
+            Ok(devices)
+        }
+        .boxed()
+    }
+
+    fn connect_device(&self, address: Address) -> BoxFuture<'_, Result<()>> {
+        async move {
+            let device = self.adapter()?.device(address)?;
+            if !device.is_connected().await? {
+                device.connect().await?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn disconnect_device(&self, address: Address) -> BoxFuture<'_, Result<()>> {
+        async move {
+            let device = self.adapter()?.device(address)?;
+            if device.is_connected().await? {
+                device.disconnect().await?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn pair_device(&self, address: Address) -> BoxFuture<'_, Result<()>> {
+        async move {
+            let device = self.adapter()?.device(address)?;
+            if !device.is_paired().await? {
+                device.pair().await?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn set_alias(&self, address: Address, alias: String) -> BoxFuture<'_, Result<()>> {
+        async move {
+            self.adapter()?.device(address)?.set_alias(alias).await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn device_details(&self, address: Address) -> BoxFuture<'_, Result<DeviceDetails>> {
+        async move {
+            let adapter = self.adapter()?;
+            let device = adapter.device(address)?;
+            let summary = read_device(&device).await;
+            let mut uuids: Vec<_> = device.uuids().await?.unwrap_or_default().into_iter().collect();
+            uuids.sort();
+            let modalias = device.modalias().await.unwrap_or(None).map(|m| {
+                format!("{}:v{:04X}p{:04X}d{:04X}", m.source, m.vendor, m.product, m.device)
+            });
+            let bonded = self.bus.device_bonded(adapter.name(), address).await.unwrap_or_else(|e| {
+                let message = format!("Failed to read Bonded for {}: {:#}", address, e);
+                eprintln!("{}", privacy::mask_text(&message));
+                None
+            });
+
+            Ok(DeviceDetails {
+                address,
+                address_type: device.address_type().await?.to_string(),
+                name: summary.name,
+                remote_name: summary.remote_name,
+                adapter: adapter.name().to_string(),
+                icon: device.icon().await.unwrap_or(None),
+                class: device.class().await.unwrap_or(None),
+                appearance: device.appearance().await.unwrap_or(None),
+                modalias,
+                rssi: device.rssi().await.unwrap_or(None),
+                tx_power: device.tx_power().await.unwrap_or(None),
+                uuids,
+                connected: summary.connected,
+                paired: summary.paired,
+                bonded,
+                trusted: summary.trusted,
+                blocked: summary.blocked,
+                legacy_pairing: device.is_legacy_pairing().await.unwrap_or(false),
+                services_resolved: device.is_services_resolved().await.unwrap_or(false),
+                battery: summary.battery,
+            })
+        }
+        .boxed()
+    }
+
+    fn remove_device(&self, address: Address) -> BoxFuture<'_, Result<()>> {
+        async move {
+            self.adapter()?.remove_device(address).await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn set_trusted(&self, address: Address, trusted: bool) -> BoxFuture<'_, Result<()>> {
+        async move {
+            self.adapter()?.device(address)?.set_trusted(trusted).await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn set_blocked(&self, address: Address, blocked: bool) -> BoxFuture<'_, Result<()>> {
+        async move {
+            self.adapter()?.device(address)?.set_blocked(blocked).await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn subscribe(&self) -> BoxFuture<'_, Result<BoxStream<'static, BluetoothEvent>>> {
+        async move {
+            let session = self.session.clone();
+            let mut session_events = session.events().await?.boxed();
+
+            // With no adapter selected only adapter hotplug is reported.
+            let adapter = self.selected()?;
+            let mut adapter_events = match &adapter {
+                Some(adapter) => adapter.events().await?.boxed(),
+                None => stream::pending().boxed(),
+            };
+
+            // Per-device property streams; each yields the device address on any
+            // change and ends when BlueZ removes the device.
+            let mut changes = SelectAll::new();
+            if let Some(adapter) = &adapter {
+                for addr in adapter.device_addresses().await? {
+                    if let Ok(events) = adapter.device(addr)?.events().await {
+                        changes.push(events.map(move |_| addr).boxed());
+                    }
+                }
+            }
+
+            let selected = self.adapter.clone();
+            // Like `discover`, stop as soon as the receiver goes away, so a
+            // subscription on a replaced session doesn't keep it alive.
+            let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+            tokio::spawn(async move {
+                loop {
+                    let event = tokio::select! {
+                        event = session_events.next() => match event {
+                            Some(SessionEvent::AdapterAdded(name)) => {
+                                let Ok(added) = session.adapter(&name) else { continue };
+                                BluetoothEvent::AdapterAdded(read_adapter(&added).await)
+                            }
+                            Some(SessionEvent::AdapterRemoved(name)) => {
+                                if let Ok(mut selected) = selected.lock() {
+                                    if selected.as_ref().is_some_and(|a| a.name() == name) {
+                                        *selected = None;
+                                    }
+                                }
+                                BluetoothEvent::AdapterRemoved(name)
+                            }
+                            None => break,
+                        },
+                        event = adapter_events.next() => match (event, &adapter) {
+                            (Some(AdapterEvent::DeviceAdded(addr)), Some(adapter)) => {
+                                let Ok(device) = adapter.device(addr) else { continue };
+                                if let Ok(events) = device.events().await {
+                                    changes.push(events.map(move |_| addr).boxed());
+                                }
+                                BluetoothEvent::DeviceAdded(read_device(&device).await)
+                            }
+                            (Some(AdapterEvent::DeviceRemoved(addr)), _) => BluetoothEvent::DeviceRemoved(addr),
+                            (Some(AdapterEvent::PropertyChanged(AdapterProperty::Powered(powered))), _) => {
+                                BluetoothEvent::PowerChanged(powered)
+                            }
+                            (Some(_), _) => continue,
+                            // The adapter went away; keep reporting hotplug.
+                            (None, _) => {
+                                adapter_events = stream::pending().boxed();
+                                continue;
+                            }
+                        },
+                        Some(addr) = changes.next(), if !changes.is_empty() => {
+                            let Some(Ok(device)) = adapter.as_ref().map(|a| a.device(addr)) else { continue };
+                            BluetoothEvent::DeviceChanged(read_device(&device).await)
+                        }
+                        () = tx.closed() => break,
+                    };
+
+                    if tx.send(event).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            Ok(channel_stream(rx))
+        }
+        .boxed()
+    }
+
+    fn discover(&self) -> BoxFuture<'_, Result<BoxStream<'static, BluetoothEvent>>> {
+        async move {
+            let adapter = self.adapter()?;
+            // Owns the discovery session; dropping it sends StopDiscovery.
+            let mut found = adapter.discover_devices().await?.boxed();
+
+            let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+            tokio::spawn(async move {
+                loop {
+                    let event = tokio::select! {
+                        event = found.next() => match event {
+                            Some(AdapterEvent::DeviceAdded(addr)) => {
+                                let Ok(device) = adapter.device(addr) else { continue };
+                                BluetoothEvent::DeviceAdded(read_device(&device).await)
+                            }
+                            Some(AdapterEvent::DeviceRemoved(addr)) => BluetoothEvent::DeviceRemoved(addr),
+                            Some(_) => continue,
+                            None => break,
+                        },
+                        () = tx.closed() => break,
+                    };
+
+                    if tx.send(event).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            Ok(channel_stream(rx))
+        }
+        .boxed()
+    }
+
+    fn pairing_requests(&self) -> BoxFuture<'_, Result<BoxStream<'static, PairingRequest>>> {
+        async move { Ok(self.pairing.listen()) }.boxed()
+    }
+}
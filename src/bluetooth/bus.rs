@@ -0,0 +1,71 @@
+use bluer::Address;
+use dbus::message::MatchRule;
+use dbus::nonblock::stdintf::org_freedesktop_dbus::Properties;
+use dbus::nonblock::{Proxy, SyncConnection};
+use futures::stream::{BoxStream, StreamExt};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use anyhow::Result;
+
+/// Well-known bus name of bluetoothd.
+const BLUEZ_NAME: &str = "org.bluez";
+
+/// Follows bluetoothd on the system bus, yielding `true` when `org.bluez`
+/// gains an owner (the daemon started) and `false` when it loses it.
+///
+/// Uses its own bus connection, so it keeps working while no bluer session
+/// exists. Must be awaited inside a tokio runtime.
+pub async fn watch_bluez() -> Result<BoxStream<'static, bool>> {
+    let (resource, conn) = dbus_tokio::connection::new_system_sync()?;
+    tokio::spawn(async move {
+        let err = resource.await;
+        eprintln!("Lost connection to the system bus: {}", err);
+    });
+
+    let rule = MatchRule::new_signal("org.freedesktop.DBus", "NameOwnerChanged")
+        .with_sender("org.freedesktop.DBus");
+    let (signal, changes) = conn.add_match(rule).await?.stream::<(String, String, String)>();
+
+    // The connection and match have to outlive the stream.
+    let keep_alive = (conn, signal);
+    Ok(changes
+        .filter_map(move |(_, (name, _old_owner, new_owner))| {
+            let _ = &keep_alive;
+            async move { (name == BLUEZ_NAME).then(|| !new_owner.is_empty()) }
+        })
+        .boxed())
+}
+
+/// System bus connection for the few properties bluer doesn't expose,
+/// opened once and shared by every call.
+pub struct SystemBus {
+    conn: Arc<SyncConnection>,
+    resource: JoinHandle<()>,
+}
+
+impl SystemBus {
+    /// Must be called inside a tokio runtime.
+    pub fn new() -> Result<Self> {
+        let (resource, conn) = dbus_tokio::connection::new_system_sync()?;
+        let resource = tokio::spawn(async move {
+            let err = resource.await;
+            eprintln!("Lost connection to the system bus: {}", err);
+        });
+        Ok(Self { conn, resource })
+    }
+
+    /// Reads the `Bonded` flag of a device. `None` if bluetoothd is older
+    /// than 5.66 and doesn't have the property.
+    pub async fn device_bonded(&self, adapter: &str, address: Address) -> Result<Option<bool>> {
+        let path = format!("/org/bluez/{}/dev_{}", adapter, address.to_string().replace(':', "_"));
+        let proxy = Proxy::new(BLUEZ_NAME, path, Duration::from_secs(5), self.conn.clone());
+        Ok(proxy.get::<bool>("org.bluez.Device1", "Bonded").await.ok())
+    }
+}
+
+impl Drop for SystemBus {
+    fn drop(&mut self) {
+        self.resource.abort();
+    }
+}
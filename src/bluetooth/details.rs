@@ -0,0 +1,97 @@
+use bluer::id::{Service, ServiceClass};
+use bluer::{Address, Uuid, UuidExt};
+
+/// Everything known about one device, read on demand for the details view
+/// rather than kept in `BluetoothDevice`.
+#[derive(Clone, Debug)]
+pub struct DeviceDetails {
+    pub address: Address,
+    /// `br/edr`, `public` or `random`.
+    pub address_type: String,
+    pub name: String,
+    pub remote_name: Option<String>,
+    /// Name of the adapter the device belongs to, e.g. `hci0`.
+    pub adapter: String,
+    pub icon: Option<String>,
+    /// Class of device, for classic (BR/EDR) devices.
+    pub class: Option<u32>,
+    /// GAP appearance, for LE devices.
+    pub appearance: Option<u16>,
+    /// Vendor and product IDs in the kernel's modalias format.
+    pub modalias: Option<String>,
+    /// Signal strength in dBm; `None` while the device is out of range.
+    pub rssi: Option<i16>,
+    pub tx_power: Option<i16>,
+    pub uuids: Vec<Uuid>,
+    pub connected: bool,
+    pub paired: bool,
+    /// `None` if bluetoothd is too old to report it.
+    pub bonded: Option<bool>,
+    pub trusted: bool,
+    pub blocked: bool,
+    pub legacy_pairing: bool,
+    pub services_resolved: bool,
+    pub battery: Option<u8>,
+}
+
+/// Profile or GATT service name for `uuid`, e.g. "Audio Sink" or "Battery".
+pub fn service_name(uuid: &Uuid) -> Option<String> {
+    if let Ok(class) = ServiceClass::try_from(*uuid) {
+        return Some(class.to_string());
+    }
+    Service::try_from(*uuid).ok().map(|service| service.to_string())
+}
+
+/// Short form for assigned UUIDs (`0x110B`), the full UUID otherwise.
+pub fn short_uuid(uuid: &Uuid) -> String {
+    match uuid.as_u16() {
+        Some(short) => format!("0x{:04X}", short),
+        None => uuid.to_string(),
+    }
+}
+
+/// Major device class of a class-of-device value, e.g. "Audio/Video".
+pub fn major_class(class: u32) -> &'static str {
+    match (class >> 8) & 0x1f {
+        0 => "Miscellaneous",
+        1 => "Computer",
+        2 => "Phone",
+        3 => "Network access point",
+        4 => "Audio/Video",
+        5 => "Peripheral",
+        6 => "Imaging",
+        7 => "Wearable",
+        8 => "Toy",
+        9 => "Health",
+        _ => "Uncategorized",
+    }
+}
+
+/// Category of a GAP appearance value, e.g. "Human Interface Device".
+pub fn appearance_category(appearance: u16) -> &'static str {
+    match appearance >> 6 {
+        0 => "Unknown",
+        1 => "Phone",
+        2 => "Computer",
+        3 => "Watch",
+        4 => "Clock",
+        5 => "Display",
+        6 => "Remote control",
+        7 => "Eyeglasses",
+        8 => "Tag",
+        9 => "Keyring",
+        10 => "Media player",
+        11 => "Barcode scanner",
+        12 => "Thermometer",
+        13 => "Heart rate sensor",
+        14 => "Blood pressure",
+        15 => "Human Interface Device",
+        16 => "Glucose meter",
+        17 => "Running/walking sensor",
+        18 => "Cycling",
+        33 => "Audio sink",
+        34 => "Audio source",
+        37 => "Wearable audio device",
+        _ => "Other",
+    }
+}
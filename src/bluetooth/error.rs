@@ -0,0 +1,200 @@
+use std::fmt;
+
+/// Why a Bluetooth operation failed, in terms the user can act on.
+///
+/// Backends keep returning `anyhow` errors; `classify` sorts them into these
+/// kinds by looking for a `BluetoothError` or a BlueZ error in the chain, and
+/// falls back to the message text BlueZ uses for connection failures.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BluetoothError {
+    /// bluetoothd is unavailable, or the adapter is off or missing; carries
+    /// why, e.g. "Bluetooth is unavailable: bluetoothd is not running".
+    NotReady(String),
+    /// Another connect, pair or power change is still running.
+    InProgress,
+    AlreadyConnected,
+    /// Pairing was rejected, cancelled, timed out or the codes did not match.
+    AuthenticationFailed,
+    /// The device did not answer, usually because it is off or out of range.
+    PageTimeout,
+    /// The device offers no profile this machine can connect, e.g. audio
+    /// without a Bluetooth-enabled sound server.
+    ProfileUnavailable,
+    NotSupported,
+    /// The radio is blocked and can't be unblocked from here, e.g. by a
+    /// hardware switch; carries why.
+    Rfkill(String),
+    /// Anything else; carries the original message.
+    Other(String),
+}
+
+impl BluetoothError {
+    /// `NotReady` when there is nothing more specific to say.
+    pub fn not_ready() -> Self {
+        Self::NotReady("Bluetooth is not ready".to_string())
+    }
+
+    pub fn classify(error: &anyhow::Error) -> Self {
+        for cause in error.chain() {
+            if let Some(error) = cause.downcast_ref::<BluetoothError>() {
+                return error.clone();
+            }
+            if let Some(error) = cause.downcast_ref::<bluer::Error>() {
+                if let Some(kind) = Self::from_bluez(error) {
+                    return kind;
+                }
+            }
+        }
+        Self::from_message(&format!("{:#}", error)).unwrap_or_else(|| Self::Other(error.to_string()))
+    }
+
+    fn from_bluez(error: &bluer::Error) -> Option<Self> {
+        use bluer::ErrorKind;
+
+        match &error.kind {
+            ErrorKind::NotReady | ErrorKind::NotAvailable => Some(Self::not_ready()),
+            ErrorKind::InProgress => Some(Self::InProgress),
+            ErrorKind::AlreadyConnected => Some(Self::AlreadyConnected),
+            ErrorKind::AuthenticationCanceled
+            | ErrorKind::AuthenticationFailed
+            | ErrorKind::AuthenticationRejected
+            | ErrorKind::AuthenticationTimeout => Some(Self::AuthenticationFailed),
+            ErrorKind::ConnectionAttemptFailed => Some(Self::PageTimeout),
+            ErrorKind::NotSupported => Some(Self::NotSupported),
+            // Connection failures arrive as `Failed` with the reason in the message.
+            _ => Self::from_message(&error.message),
+        }
+    }
+
+    /// Matches the reasons bluetoothd puts in `org.bluez.Error.Failed`, e.g.
+    /// `br-connection-page-timeout` or the older `Page Timeout`.
+    fn from_message(message: &str) -> Option<Self> {
+        let lowercase = message.to_lowercase();
+        let has = |needle: &str| lowercase.contains(needle);
+
+        if has("page-timeout") || has("page timeout") || has("host is down") {
+            Some(Self::PageTimeout)
+        } else if has("profile-unavailable") || has("protocol not available") {
+            Some(Self::ProfileUnavailable)
+        } else if has("authentication") {
+            Some(Self::AuthenticationFailed)
+        } else if has("already-connected") || has("already connected") {
+            Some(Self::AlreadyConnected)
+        } else if has("in progress") || has("busy") {
+            Some(Self::InProgress)
+        } else if has("not ready") || has("powered off") || has("no bluetooth adapter") {
+            Some(Self::NotReady(message.to_string()))
+        } else if has("not supported") {
+            Some(Self::NotSupported)
+        } else {
+            None
+        }
+    }
+
+    /// Stable identifier for scripts, e.g. `page-timeout`. Unlike the
+    /// message, it never changes wording.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::NotReady(_) => "not-ready",
+            Self::InProgress => "in-progress",
+            Self::AlreadyConnected => "already-connected",
+            Self::AuthenticationFailed => "authentication-failed",
+            Self::PageTimeout => "page-timeout",
+            Self::ProfileUnavailable => "profile-unavailable",
+            Self::NotSupported => "not-supported",
+            Self::Rfkill(_) => "rfkill",
+            Self::Other(_) => "other",
+        }
+    }
+
+    /// One-line description for the user.
+    pub fn message(&self) -> &str {
+        match self {
+            Self::NotReady(reason) => reason,
+            Self::InProgress => "Another operation is still in progress",
+            Self::AlreadyConnected => "The device is already connected",
+            Self::AuthenticationFailed => "Pairing failed",
+            Self::PageTimeout => "The device did not respond",
+            Self::ProfileUnavailable => "The device has no service this computer can use",
+            Self::NotSupported => "This isn't supported by the device or adapter",
+            Self::Rfkill(reason) => reason,
+            Self::Other(message) => message,
+        }
+    }
+
+    /// What the user can try next, if anything.
+    pub fn action(&self) -> Option<&'static str> {
+        match self {
+            Self::NotReady(_) => Some("Turn Bluetooth on and try again."),
+            Self::InProgress => Some("Wait a moment and try again."),
+            Self::AlreadyConnected => None,
+            Self::AuthenticationFailed => Some("Put the device in pairing mode and pair again."),
+            Self::PageTimeout => Some("Make sure the device is switched on and in range."),
+            Self::ProfileUnavailable => {
+                Some("For audio devices, check that the sound server's Bluetooth support is running.")
+            }
+            Self::NotSupported => None,
+            Self::Rfkill(_) => Some("Check the wireless switch or airplane mode."),
+            Self::Other(_) => Some("Try again, or restart Bluetooth if it keeps failing."),
+        }
+    }
+}
+
+impl fmt::Display for BluetoothError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for BluetoothError {}
+
+impl From<anyhow::Error> for BluetoothError {
+    fn from(error: anyhow::Error) -> Self {
+        Self::classify(&error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_bluez_failure_messages() {
+        let cases = [
+            ("br-connection-page-timeout", BluetoothError::PageTimeout),
+            ("Page Timeout", BluetoothError::PageTimeout),
+            ("Host is down", BluetoothError::PageTimeout),
+            ("br-connection-profile-unavailable", BluetoothError::ProfileUnavailable),
+            ("Protocol not available", BluetoothError::ProfileUnavailable),
+            ("Authentication Rejected", BluetoothError::AuthenticationFailed),
+            ("br-connection-already-connected", BluetoothError::AlreadyConnected),
+            ("Already Connected", BluetoothError::AlreadyConnected),
+            ("Operation already in progress", BluetoothError::InProgress),
+            ("Device or resource busy", BluetoothError::InProgress),
+            ("Resource Not Ready", BluetoothError::NotReady("Resource Not Ready".to_string())),
+            ("Adapter is powered off", BluetoothError::NotReady("Adapter is powered off".to_string())),
+            ("No Bluetooth adapter", BluetoothError::NotReady("No Bluetooth adapter".to_string())),
+            ("Operation is not supported", BluetoothError::NotSupported),
+        ];
+        for (message, expected) in cases {
+            assert_eq!(BluetoothError::from_message(message), Some(expected), "{}", message);
+        }
+        assert_eq!(BluetoothError::from_message("Input/output error"), None);
+    }
+
+    #[test]
+    fn classify_prefers_an_error_in_the_chain() {
+        let error = anyhow::Error::new(BluetoothError::NotReady("Bluetooth is unavailable: gone".to_string()))
+            .context("page timeout while connecting");
+        assert_eq!(
+            BluetoothError::classify(&error),
+            BluetoothError::NotReady("Bluetooth is unavailable: gone".to_string())
+        );
+    }
+
+    #[test]
+    fn classify_keeps_unknown_messages() {
+        let error = anyhow::anyhow!("Something odd");
+        assert_eq!(BluetoothError::classify(&error), BluetoothError::Other("Something odd".to_string()));
+    }
+}
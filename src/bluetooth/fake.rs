@@ -0,0 +1,612 @@
+use bluer::{Address, Uuid, UuidExt};
+use futures::channel::mpsc::{self, UnboundedSender};
+use futures::future::{BoxFuture, FutureExt};
+use futures::stream::{BoxStream, StreamExt};
+use std::sync::{Arc, Mutex, Once};
+use std::time::Duration;
+use anyhow::{anyhow, bail, Context, Result};
+
+use super::agent::{PairingAgent, PairingRequest};
+use super::scenario::{Scenario, ScenarioPairing};
+use super::{
+    channel_stream, AdapterInfo, BluetoothBackend, BluetoothDevice, BluetoothError, BluetoothEvent, DeviceDetails,
+};
+
+/// The single adapter a simulation has.
+const ADAPTER_NAME: &str = "hci0";
+
+/// In-memory backend for running the UI without an adapter or bluetoothd.
+///
+/// Devices, latencies and failures come from a [`Scenario`]; calls change the
+/// simulated state the way bluetoothd would change the real one.
+pub struct FakeBackend {
+    state: Arc<Mutex<FakeState>>,
+    subscribers: Arc<Subscribers>,
+    pairing: PairingAgent,
+    power_latency: Duration,
+    battery_drain: Once,
+}
+
+struct FakeState {
+    powered: bool,
+    devices: Vec<FakeDevice>,
+}
+
+struct FakeDevice {
+    device: BluetoothDevice,
+    /// While connected, the battery loses 1% per interval.
+    battery_drain: Option<Duration>,
+    connect_latency: Duration,
+    pair_latency: Duration,
+    fail_connect: Option<String>,
+    fail_pair: Option<String>,
+    pairing: Option<ScenarioPairing>,
+    /// Devices with a discovery delay stay invisible until a scan finds them.
+    discovery_delay: Option<Duration>,
+    visible: bool,
+    uuids: Vec<Uuid>,
+    class: Option<u32>,
+    rssi: Option<i16>,
+}
+
+#[derive(Default)]
+struct Subscribers(Mutex<Vec<UnboundedSender<BluetoothEvent>>>);
+
+impl Subscribers {
+    /// Sends `event` to every live subscriber, dropping the ones that went away.
+    fn emit(&self, event: BluetoothEvent) {
+        if let Ok(mut subscribers) = self.0.lock() {
+            subscribers.retain(|tx| tx.unbounded_send(event.clone()).is_ok());
+        }
+    }
+}
+
+impl FakeBackend {
+    pub fn from_scenario(scenario: Scenario) -> Result<Self> {
+        let mut devices = Vec::new();
+        for d in scenario.devices {
+            let address: Address = d
+                .address
+                .parse()
+                .with_context(|| format!("Invalid address {:?} in scenario", d.address))?;
+            let uuids = d
+                .uuids
+                .iter()
+                .map(|uuid| match u16::from_str_radix(uuid, 16) {
+                    Ok(short) => Ok(Uuid::from_u16(short)),
+                    Err(_) => uuid.parse().with_context(|| format!("Invalid UUID {:?} in scenario", uuid)),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            devices.push(FakeDevice {
+                device: BluetoothDevice {
+                    address,
+                    name: d.alias.unwrap_or_else(|| d.name.clone()),
+                    remote_name: Some(d.name),
+                    icon: d.icon,
+                    connected: d.connected && scenario.powered,
+                    paired: d.paired,
+                    trusted: d.trusted,
+                    blocked: d.blocked,
+                    battery: d.battery,
+                },
+                battery_drain: d.battery_drain_ms.map(Duration::from_millis),
+                connect_latency: Duration::from_millis(d.connect_latency_ms),
+                pair_latency: Duration::from_millis(d.pair_latency_ms),
+                fail_connect: d.fail_connect,
+                fail_pair: d.fail_pair,
+                pairing: d.pairing,
+                discovery_delay: d.discovery_delay_ms.map(Duration::from_millis),
+                visible: d.discovery_delay_ms.is_none(),
+                uuids,
+                class: d.class,
+                rssi: d.rssi,
+            });
+        }
+
+        Ok(Self {
+            state: Arc::new(Mutex::new(FakeState {
+                powered: scenario.powered,
+                devices,
+            })),
+            subscribers: Arc::default(),
+            pairing: PairingAgent::default(),
+            power_latency: Duration::from_millis(scenario.power_latency_ms),
+            battery_drain: Once::new(),
+        })
+    }
+
+    fn emit(&self, event: BluetoothEvent) {
+        self.subscribers.emit(event);
+    }
+
+    /// Runs the agent exchange a real device would trigger during pairing.
+    async fn authenticate(&self, address: Address, method: ScenarioPairing) -> Result<()> {
+        match method {
+            ScenarioPairing::PinCode { expected } => {
+                let pin = self.pairing.request_pin_code(address).await;
+                if pin.map_err(|e| anyhow!("Authentication {}", e))? != expected {
+                    bail!("Authentication Failed");
+                }
+            }
+            ScenarioPairing::Passkey { expected } => {
+                let passkey = self.pairing.request_passkey(address).await;
+                if passkey.map_err(|e| anyhow!("Authentication {}", e))? != expected {
+                    bail!("Authentication Failed");
+                }
+            }
+            ScenarioPairing::DisplayPasskey { passkey, typing_ms } => {
+                // Mirror bluetoothd: re-send the prompt as each digit is "typed".
+                let per_digit = Duration::from_millis(typing_ms / 6);
+                let mut shown = None;
+                for entered in 0..=6u16 {
+                    let guard = self
+                        .pairing
+                        .display_passkey(address, passkey, entered)
+                        .map_err(|e| anyhow!("Authentication {}", e))?;
+                    // The previous prompt closes only once its replacement is out.
+                    shown = Some(guard);
+                    tokio::time::sleep(per_digit).await;
+                }
+                drop(shown);
+            }
+            ScenarioPairing::Confirmation { passkey } => {
+                self.pairing
+                    .request_confirmation(address, passkey)
+                    .await
+                    .map_err(|e| anyhow!("Authentication {}", e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts draining the batteries of connected devices that have a drain
+    /// rate. Runs for the lifetime of the runtime; started on first subscribe
+    /// since the backend is built before the runtime exists.
+    fn start_battery_drain(&self) {
+        let state = self.state.clone();
+        let subscribers = self.subscribers.clone();
+        let rates: Vec<(Address, Duration)> = match self.lock() {
+            Ok(state) => state
+                .devices
+                .iter()
+                .filter_map(|d| d.battery_drain.map(|rate| (d.device.address, rate)))
+                .collect(),
+            Err(_) => return,
+        };
+
+        for (address, rate) in rates {
+            let state = state.clone();
+            let subscribers = subscribers.clone();
+            tokio::spawn(async move {
+                let mut ticks = tokio::time::interval(rate);
+                ticks.tick().await;
+                loop {
+                    ticks.tick().await;
+                    let drained = state.lock().ok().and_then(|mut state| {
+                        let d = state.devices.iter_mut().find(|d| d.device.address == address)?;
+                        let level = d.device.battery.filter(|level| *level > 0 && d.device.connected)?;
+                        d.device.battery = Some(level - 1);
+                        Some(d.device.clone())
+                    });
+                    if let Some(device) = drained {
+                        subscribers.emit(BluetoothEvent::DeviceChanged(device));
+                    }
+                }
+            });
+        }
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, FakeState>> {
+        self.state.lock().map_err(|_| anyhow!("Fake backend state poisoned"))
+    }
+
+    /// Applies `f` to the device with `address`, failing the way bluetoothd
+    /// would if the adapter is off or the device is unknown.
+    fn with_device<T>(&self, address: Address, f: impl FnOnce(&mut FakeDevice) -> Result<T>) -> Result<T> {
+        let mut state = self.lock()?;
+        if !state.powered {
+            return Err(BluetoothError::NotReady("Adapter is powered off".to_string()).into());
+        }
+        let device = state
+            .devices
+            .iter_mut()
+            .find(|d| d.visible && d.device.address == address)
+            .ok_or_else(|| anyhow!("Device {} does not exist", address))?;
+        f(device)
+    }
+}
+
+impl Default for FakeBackend {
+    fn default() -> Self {
+        Self::from_scenario(Scenario::default()).expect("built-in scenario is valid")
+    }
+}
+
+impl BluetoothBackend for FakeBackend {
+    fn adapters(&self) -> BoxFuture<'_, Result<Vec<AdapterInfo>>> {
+        async move {
+            Ok(vec![AdapterInfo {
+                name: ADAPTER_NAME.to_string(),
+                alias: "Simulated Adapter".to_string(),
+                address: Address::any(),
+            }])
+        }
+        .boxed()
+    }
+
+    fn current_adapter(&self) -> BoxFuture<'_, Result<Option<String>>> {
+        async move { Ok(Some(ADAPTER_NAME.to_string())) }.boxed()
+    }
+
+    fn select_adapter(&self, name: String) -> BoxFuture<'_, Result<()>> {
+        async move {
+            if name != ADAPTER_NAME {
+                bail!("Adapter {} is not present", name);
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn is_powered(&self) -> BoxFuture<'_, Result<bool>> {
+        async move { Ok(self.lock()?.powered) }.boxed()
+    }
+
+    fn set_powered(&self, powered: bool) -> BoxFuture<'_, Result<()>> {
+        async move {
+            tokio::time::sleep(self.power_latency).await;
+            let dropped = {
+                let mut state = self.lock()?;
+                state.powered = powered;
+                let mut dropped = Vec::new();
+                if !powered {
+                    for d in state.devices.iter_mut().filter(|d| d.device.connected) {
+                        d.device.connected = false;
+                        dropped.push(d.device.clone());
+                    }
+                }
+                dropped
+            };
+
+            self.emit(BluetoothEvent::PowerChanged(powered));
+            for device in dropped {
+                self.emit(BluetoothEvent::DeviceChanged(device));
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn get_devices(&self) -> BoxFuture<'_, Result<Vec<BluetoothDevice>>> {
+        async move {
+            let state = self.lock()?;
+            Ok(state.devices.iter().filter(|d| d.visible).map(|d| d.device.clone()).collect())
+        }
+        .boxed()
+    }
+
+    fn connect_device(&self, address: Address) -> BoxFuture<'_, Result<()>> {
+        async move {
+            let latency = self.with_device(address, |d| Ok(d.connect_latency))?;
+            tokio::time::sleep(latency).await;
+            let device = self.with_device(address, |d| {
+                if d.device.blocked {
+                    bail!("Device is blocked");
+                }
+                if let Some(message) = &d.fail_connect {
+                    bail!("{}", message);
+                }
+                d.device.connected = true;
+                Ok(d.device.clone())
+            })?;
+            self.emit(BluetoothEvent::DeviceChanged(device));
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn disconnect_device(&self, address: Address) -> BoxFuture<'_, Result<()>> {
+        async move {
+            let device = self.with_device(address, |d| {
+                d.device.connected = false;
+                Ok(d.device.clone())
+            })?;
+            self.emit(BluetoothEvent::DeviceChanged(device));
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn pair_device(&self, address: Address) -> BoxFuture<'_, Result<()>> {
+        async move {
+            let (latency, method) = self.with_device(address, |d| Ok((d.pair_latency, d.pairing.clone())))?;
+            tokio::time::sleep(latency).await;
+            if let Some(method) = method {
+                self.authenticate(address, method).await?;
+            }
+            let device = self.with_device(address, |d| {
+                if let Some(message) = &d.fail_pair {
+                    bail!("{}", message);
+                }
+                d.device.paired = true;
+                Ok(d.device.clone())
+            })?;
+            self.emit(BluetoothEvent::DeviceChanged(device));
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn set_alias(&self, address: Address, alias: String) -> BoxFuture<'_, Result<()>> {
+        async move {
+            let device = self.with_device(address, |d| {
+                d.device.name = match alias.is_empty() {
+                    true => d.device.remote_name.clone().unwrap_or_default(),
+                    false => alias,
+                };
+                Ok(d.device.clone())
+            })?;
+            self.emit(BluetoothEvent::DeviceChanged(device));
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn device_details(&self, address: Address) -> BoxFuture<'_, Result<DeviceDetails>> {
+        async move {
+            self.with_device(address, |d| {
+                let device = d.device.clone();
+                Ok(DeviceDetails {
+                    address,
+                    address_type: "br/edr".to_string(),
+                    name: device.name,
+                    remote_name: device.remote_name,
+                    adapter: ADAPTER_NAME.to_string(),
+                    icon: Some(device.icon),
+                    class: d.class,
+                    appearance: None,
+                    modalias: None,
+                    rssi: d.rssi,
+                    tx_power: None,
+                    uuids: d.uuids.clone(),
+                    connected: device.connected,
+                    paired: device.paired,
+                    bonded: Some(device.paired),
+                    trusted: device.trusted,
+                    blocked: device.blocked,
+                    legacy_pairing: matches!(d.pairing, Some(ScenarioPairing::PinCode { .. })),
+                    services_resolved: device.connected,
+                    battery: device.battery,
+                })
+            })
+        }
+        .boxed()
+    }
+
+    fn remove_device(&self, address: Address) -> BoxFuture<'_, Result<()>> {
+        async move {
+            self.with_device(address, |d| {
+                // Out of sight until the next scan finds it again
+                d.visible = false;
+                d.discovery_delay = Some(d.discovery_delay.unwrap_or_default());
+                d.device.connected = false;
+                d.device.paired = false;
+                d.device.trusted = false;
+                Ok(())
+            })?;
+            self.emit(BluetoothEvent::DeviceRemoved(address));
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn set_trusted(&self, address: Address, trusted: bool) -> BoxFuture<'_, Result<()>> {
+        async move {
+            let device = self.with_device(address, |d| {
+                d.device.trusted = trusted;
+                Ok(d.device.clone())
+            })?;
+            self.emit(BluetoothEvent::DeviceChanged(device));
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn set_blocked(&self, address: Address, blocked: bool) -> BoxFuture<'_, Result<()>> {
+        async move {
+            let device = self.with_device(address, |d| {
+                d.device.blocked = blocked;
+                // Blocking drops the connection, as bluetoothd does
+                if blocked {
+                    d.device.connected = false;
+                }
+                Ok(d.device.clone())
+            })?;
+            self.emit(BluetoothEvent::DeviceChanged(device));
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn subscribe(&self) -> BoxFuture<'_, Result<BoxStream<'static, BluetoothEvent>>> {
+        async move {
+            self.battery_drain.call_once(|| self.start_battery_drain());
+            let (tx, rx) = mpsc::unbounded();
+            self.subscribers
+                .0
+                .lock()
+                .map_err(|_| anyhow!("Fake backend state poisoned"))?
+                .push(tx);
+            Ok(rx.boxed())
+        }
+        .boxed()
+    }
+
+    fn discover(&self) -> BoxFuture<'_, Result<BoxStream<'static, BluetoothEvent>>> {
+        async move {
+            let (known, mut hidden) = {
+                let state = self.lock()?;
+                if !state.powered {
+                    bail!("Adapter is powered off");
+                }
+                let known: Vec<BluetoothDevice> =
+                    state.devices.iter().filter(|d| d.visible).map(|d| d.device.clone()).collect();
+                let hidden: Vec<(Address, Duration)> = state
+                    .devices
+                    .iter()
+                    .filter(|d| !d.visible)
+                    .filter_map(|d| d.discovery_delay.map(|delay| (d.device.address, delay)))
+                    .collect();
+                (known, hidden)
+            };
+            hidden.sort_by_key(|(_, delay)| *delay);
+
+            let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+            for device in known {
+                let _ = tx.send(BluetoothEvent::DeviceAdded(device));
+            }
+
+            let state = self.state.clone();
+            let subscribers = self.subscribers.clone();
+            tokio::spawn(async move {
+                let started = tokio::time::Instant::now();
+                for (address, delay) in hidden {
+                    tokio::select! {
+                        () = tokio::time::sleep_until(started + delay) => {}
+                        () = tx.closed() => return,
+                    }
+
+                    let found = state.lock().ok().and_then(|mut state| {
+                        let d = state.devices.iter_mut().find(|d| d.device.address == address)?;
+                        d.visible = true;
+                        Some(d.device.clone())
+                    });
+                    if let Some(device) = found {
+                        subscribers.emit(BluetoothEvent::DeviceAdded(device.clone()));
+                        if tx.send(BluetoothEvent::DeviceAdded(device)).is_err() {
+                            return;
+                        }
+                    }
+                }
+                // Keep "scanning" until the caller stops, like a real adapter.
+                tx.closed().await;
+            });
+
+            Ok(channel_stream(rx))
+        }
+        .boxed()
+    }
+
+    fn pairing_requests(&self) -> BoxFuture<'_, Result<BoxStream<'static, PairingRequest>>> {
+        async move { Ok(self.pairing.listen()) }.boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use tokio::time::Instant;
+
+    use crate::bluetooth::BluetoothService;
+    use crate::config::{BackendKind, Config};
+
+    const HEADPHONES: &str = "00:1A:7D:DA:71:01";
+    const KEYBOARD: &str = "00:1A:7D:DA:71:02";
+    const SPEAKER: &str = "00:1A:7D:DA:71:03";
+    const PHONE: &str = "00:1A:7D:DA:71:04";
+
+    fn demo() -> FakeBackend {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios/demo.json");
+        FakeBackend::from_scenario(Scenario::load(&path).unwrap()).unwrap()
+    }
+
+    async fn device(backend: &FakeBackend, address: &str) -> BluetoothDevice {
+        let address: Address = address.parse().unwrap();
+        let devices = backend.get_devices().await.unwrap();
+        devices.into_iter().find(|d| d.address == address).expect("device is listed")
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn connect_takes_the_scenario_latency() {
+        let backend = demo();
+        let start = Instant::now();
+        backend.connect_device(KEYBOARD.parse().unwrap()).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(400));
+        assert!(device(&backend, KEYBOARD).await.connected);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fail_connect_is_classified_after_the_latency() {
+        let backend = demo();
+        let start = Instant::now();
+        let error = backend.connect_device(SPEAKER.parse().unwrap()).await.unwrap_err();
+        assert!(start.elapsed() >= Duration::from_millis(5000));
+        assert_eq!(BluetoothError::classify(&error), BluetoothError::PageTimeout);
+        assert!(!device(&backend, SPEAKER).await.connected);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fail_pair_is_classified_after_the_latency() {
+        let backend = demo();
+        let start = Instant::now();
+        let error = backend.pair_device(PHONE.parse().unwrap()).await.unwrap_err();
+        assert!(start.elapsed() >= Duration::from_millis(2000));
+        assert_eq!(BluetoothError::classify(&error), BluetoothError::AuthenticationFailed);
+        assert!(!device(&backend, PHONE).await.paired);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn power_off_drops_connections_and_refuses_new_ones() {
+        let backend = demo();
+        let mut events = backend.subscribe().await.unwrap();
+        backend.set_powered(false).await.unwrap();
+
+        assert!(matches!(events.next().await, Some(BluetoothEvent::PowerChanged(false))));
+        assert!(matches!(events.next().await, Some(BluetoothEvent::DeviceChanged(d)) if !d.connected));
+        assert!(!device(&backend, HEADPHONES).await.connected);
+
+        let error = backend.connect_device(KEYBOARD.parse().unwrap()).await.unwrap_err();
+        let expected = BluetoothError::NotReady("Adapter is powered off".to_string());
+        assert_eq!(BluetoothError::classify(&error), expected);
+    }
+
+    #[tokio::test]
+    async fn battery_drain_reaches_service_subscribers() {
+        let scenario = std::env::temp_dir().join(format!("bluetooth-widget-drain-{}.json", std::process::id()));
+        let json = r#"{ "devices": [{ "address": "00:1A:7D:DA:71:01", "name": "Headphones",
+            "connected": true, "battery": 50, "battery_drain_ms": 20 }] }"#;
+        std::fs::write(&scenario, json).unwrap();
+        let config = Config {
+            backend: BackendKind::Simulation,
+            simulation_scenario: Some(scenario.clone()),
+            ..Config::default()
+        };
+
+        let service = BluetoothService::headless(&config, false);
+        let mut events = service.subscribe().await.unwrap();
+        let event = tokio::time::timeout(Duration::from_secs(5), events.next()).await;
+        let _ = std::fs::remove_file(&scenario);
+        match event {
+            Ok(Some(BluetoothEvent::DeviceChanged(device))) => assert_eq!(device.battery, Some(49)),
+            other => panic!("expected the battery to drain, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn powering_on_refuses_a_hard_block() {
+        let rfkill = std::env::temp_dir().join(format!("bluetooth-widget-hard-block-{}", std::process::id()));
+        // One Bluetooth radio, added hard-blocked
+        std::fs::write(&rfkill, [0, 0, 0, 0, 2, 0, 0, 1]).unwrap();
+        let config = Config {
+            backend: BackendKind::Simulation,
+            rfkill_path: rfkill.clone(),
+            ..Config::default()
+        };
+
+        let service = BluetoothService::headless(&config, false);
+        let result = service.set_powered(true).await;
+        let _ = std::fs::remove_file(&rfkill);
+        assert!(matches!(result, Err(BluetoothError::Rfkill(_))));
+    }
+}
@@ -0,0 +1,250 @@
+mod agent;
+mod bluez;
+mod bus;
+mod details;
+mod error;
+mod fake;
+mod rfkill;
+mod scenario;
+mod service;
+
+use bluer::Address;
+use futures::future::BoxFuture;
+use futures::stream::{BoxStream, StreamExt};
+use std::cmp::Ordering;
+use anyhow::Result;
+
+use crate::config::PinnedDevice;
+
+pub use agent::{PairingReply, PairingRequest, PAIRING_TIMEOUT};
+pub use bluez::BluezBackend;
+pub use details::{appearance_category, major_class, service_name, short_uuid, DeviceDetails};
+pub use error::BluetoothError;
+pub use fake::FakeBackend;
+pub use rfkill::{Rfkill, RfkillState, RFKILL_PATH};
+pub use scenario::Scenario;
+pub use service::{BluetoothService, ServiceState};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BluetoothDevice {
+    pub address: Address,
+    /// Display name: the alias set by the user, or the remote name without one.
+    pub name: String,
+    /// Name the device advertises, if known.
+    pub remote_name: Option<String>,
+    pub icon: String,
+    pub connected: bool,
+    pub paired: bool,
+    /// Connections from the device are accepted without asking.
+    pub trusted: bool,
+    /// Connections from the device are rejected.
+    pub blocked: bool,
+    /// Charge in percent, for devices that expose the Battery1 interface.
+    pub battery: Option<u8>,
+}
+
+impl BluetoothDevice {
+    pub fn get_icon_name(&self) -> String {
+        match self.icon.as_str() {
+            "audio-card" | "audio-headset" => "audio-headphones".to_string(),
+            "input-keyboard" => "input-keyboard".to_string(),
+            "input-mouse" => "input-mouse".to_string(),
+            "input-tablet" => "input-tablet".to_string(),
+            "phone" => "phone".to_string(),
+            "computer" => "computer".to_string(),
+            "camera-photo" => "camera-photo".to_string(),
+            "camera-video" => "camera-video".to_string(),
+            _ => "bluetooth".to_string(),
+        }
+    }
+
+    /// Symbolic battery icon for the nearest 10% step, e.g. `battery-level-70-symbolic`.
+    pub fn battery_icon_name(&self) -> Option<String> {
+        self.battery
+            .map(|level| format!("battery-level-{}-symbolic", (u32::from(level.min(100)) + 5) / 10 * 10))
+    }
+}
+
+/// A local Bluetooth controller, e.g. an internal chip or a USB dongle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdapterInfo {
+    /// Kernel name such as `hci0`; stable across restarts, so it is what the config stores.
+    pub name: String,
+    pub alias: String,
+    pub address: Address,
+}
+
+/// Display order: pinned devices in the user's order, then connected, then
+/// paired, then name.
+pub fn compare_devices(a: &BluetoothDevice, b: &BluetoothDevice, pinned: &[PinnedDevice]) -> Ordering {
+    let rank = |device: &BluetoothDevice| pinned.iter().position(|pin| pin.matches(device));
+    match (rank(a), rank(b)) {
+        (Some(a), Some(b)) if a != b => return a.cmp(&b),
+        (Some(_), None) => return Ordering::Less,
+        (None, Some(_)) => return Ordering::Greater,
+        _ => {}
+    }
+
+    if a.connected != b.connected {
+        return b.connected.cmp(&a.connected);
+    }
+
+    if a.paired != b.paired {
+        return b.paired.cmp(&a.paired);
+    }
+
+    a.name.cmp(&b.name)
+}
+
+/// Change notifications pushed by a backend, so the UI does not have to poll.
+#[derive(Clone, Debug)]
+pub enum BluetoothEvent {
+    DeviceAdded(BluetoothDevice),
+    DeviceChanged(BluetoothDevice),
+    DeviceRemoved(Address),
+    PowerChanged(bool),
+    AdapterAdded(AdapterInfo),
+    /// Carries the adapter name. If it was the selected adapter, the backend
+    /// has no adapter until another one is selected.
+    AdapterRemoved(String),
+}
+
+/// Everything the widget needs from a Bluetooth stack.
+///
+/// `BluezBackend` talks to bluetoothd; `FakeBackend` keeps devices in memory
+/// so the UI can run on machines without an adapter.
+pub trait BluetoothBackend: Send + Sync {
+    fn adapters(&self) -> BoxFuture<'_, Result<Vec<AdapterInfo>>>;
+    /// Name of the adapter the other calls act on, or `None` if it was removed.
+    fn current_adapter(&self) -> BoxFuture<'_, Result<Option<String>>>;
+    /// Switches to adapter `name`. Streams from earlier `subscribe` and
+    /// `discover` calls stay on the previous adapter.
+    fn select_adapter(&self, name: String) -> BoxFuture<'_, Result<()>>;
+    fn is_powered(&self) -> BoxFuture<'_, Result<bool>>;
+    fn set_powered(&self, powered: bool) -> BoxFuture<'_, Result<()>>;
+    fn get_devices(&self) -> BoxFuture<'_, Result<Vec<BluetoothDevice>>>;
+    fn connect_device(&self, address: Address) -> BoxFuture<'_, Result<()>>;
+    fn disconnect_device(&self, address: Address) -> BoxFuture<'_, Result<()>>;
+    fn pair_device(&self, address: Address) -> BoxFuture<'_, Result<()>>;
+    /// Sets the local alias; an empty alias goes back to the remote name.
+    fn set_alias(&self, address: Address, alias: String) -> BoxFuture<'_, Result<()>>;
+    /// Reads every property of one device for the details view.
+    fn device_details(&self, address: Address) -> BoxFuture<'_, Result<DeviceDetails>>;
+    /// Forgets the device, including its pairing. Nearby devices come back
+    /// with the next scan.
+    fn remove_device(&self, address: Address) -> BoxFuture<'_, Result<()>>;
+    fn set_trusted(&self, address: Address, trusted: bool) -> BoxFuture<'_, Result<()>>;
+    fn set_blocked(&self, address: Address, blocked: bool) -> BoxFuture<'_, Result<()>>;
+    /// Streams changes to the selected adapter and its devices, plus adapters
+    /// being added or removed, until the returned stream is dropped.
+    fn subscribe(&self) -> BoxFuture<'_, Result<BoxStream<'static, BluetoothEvent>>>;
+    /// Scans for devices, yielding `DeviceAdded` for known and newly found
+    /// devices. Discovery stops when the returned stream is dropped.
+    fn discover(&self) -> BoxFuture<'_, Result<BoxStream<'static, BluetoothEvent>>>;
+    /// Delivers pairing agent prompts (PIN, passkey, confirmation, service
+    /// authorization). Only the most recent stream receives requests.
+    fn pairing_requests(&self) -> BoxFuture<'_, Result<BoxStream<'static, PairingRequest>>>;
+}
+
+/// Adapts a tokio channel fed by a runtime task into a stream that can be
+/// polled from any executor, including the GTK main context.
+fn channel_stream<T: Send + 'static>(rx: tokio::sync::mpsc::UnboundedReceiver<T>) -> BoxStream<'static, T> {
+    futures::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|item| (item, rx)) }).boxed()
+}
+
+#[cfg(test)]
+impl BluetoothDevice {
+    /// A paired device that isn't connected; tests set the fields they vary
+    /// with struct update syntax.
+    pub fn test(address: &str, name: &str) -> Self {
+        Self {
+            address: address.parse().unwrap(),
+            name: name.to_string(),
+            remote_name: None,
+            icon: "bluetooth".to_string(),
+            connected: false,
+            paired: true,
+            trusted: false,
+            blocked: false,
+            battery: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connected(address: &str, name: &str) -> BluetoothDevice {
+        BluetoothDevice {
+            connected: true,
+            ..BluetoothDevice::test(address, name)
+        }
+    }
+
+    fn unpaired(address: &str, name: &str) -> BluetoothDevice {
+        BluetoothDevice {
+            paired: false,
+            ..BluetoothDevice::test(address, name)
+        }
+    }
+
+    fn pin(address: &str, name: Option<&str>) -> PinnedDevice {
+        PinnedDevice {
+            address: address.to_string(),
+            name: name.map(str::to_string),
+        }
+    }
+
+    fn sorted(mut devices: Vec<BluetoothDevice>, pinned: &[PinnedDevice]) -> Vec<String> {
+        devices.sort_by(|a, b| compare_devices(a, b, pinned));
+        devices.into_iter().map(|device| device.name).collect()
+    }
+
+    #[test]
+    fn pins_match_by_address_or_name() {
+        let earbuds = BluetoothDevice::test("00:1A:7D:DA:71:01", "Earbuds");
+        assert!(pin("00:1a:7d:da:71:01", None).matches(&earbuds));
+        // Reset earbuds come back with a new address but the same name
+        assert!(pin("00:1A:7D:DA:71:99", Some("Earbuds")).matches(&earbuds));
+        assert!(!pin("00:1A:7D:DA:71:99", Some("Headphones")).matches(&earbuds));
+        assert!(!pin("00:1A:7D:DA:71:99", None).matches(&earbuds));
+    }
+
+    #[test]
+    fn pinned_devices_come_first_in_the_users_order() {
+        let devices = vec![
+            connected("00:1A:7D:DA:71:01", "Headphones"),
+            BluetoothDevice::test("00:1A:7D:DA:71:02", "Keyboard"),
+            unpaired("00:1A:7D:DA:71:03", "Mouse"),
+        ];
+        let pinned = [pin("00:1A:7D:DA:71:03", None), pin("00:1A:7D:DA:71:99", Some("Keyboard"))];
+        assert_eq!(sorted(devices, &pinned), ["Mouse", "Keyboard", "Headphones"]);
+    }
+
+    #[test]
+    fn unpinned_devices_sort_by_connected_then_paired_then_name() {
+        let devices = vec![
+            unpaired("00:1A:7D:DA:71:01", "Speaker"),
+            BluetoothDevice::test("00:1A:7D:DA:71:02", "Mouse"),
+            BluetoothDevice::test("00:1A:7D:DA:71:03", "Keyboard"),
+            connected("00:1A:7D:DA:71:04", "Watch"),
+            unpaired("00:1A:7D:DA:71:05", "Phone"),
+        ];
+        assert_eq!(sorted(devices, &[]), ["Watch", "Keyboard", "Mouse", "Phone", "Speaker"]);
+    }
+
+    #[test]
+    fn a_device_matching_two_pins_takes_the_first() {
+        let devices = vec![
+            BluetoothDevice::test("00:1A:7D:DA:71:01", "Headphones"),
+            BluetoothDevice::test("00:1A:7D:DA:71:02", "Keyboard"),
+        ];
+        let pinned = [
+            pin("00:1A:7D:DA:71:02", None),
+            pin("00:1A:7D:DA:71:01", None),
+            pin("00:1A:7D:DA:71:99", Some("Keyboard")),
+        ];
+        assert_eq!(sorted(devices, &pinned), ["Keyboard", "Headphones"]);
+    }
+}
@@ -0,0 +1,267 @@
+use futures::channel::mpsc;
+use futures::stream::{BoxStream, StreamExt};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::thread;
+use anyhow::{Context, Result};
+
+/// The kernel's rfkill control device.
+pub const RFKILL_PATH: &str = "/dev/rfkill";
+
+const TYPE_BLUETOOTH: u8 = 2;
+const OP_ADD: u8 = 0;
+const OP_DEL: u8 = 1;
+const OP_CHANGE: u8 = 2;
+const OP_CHANGE_ALL: u8 = 3;
+/// Size of the original `struct rfkill_event`; asking for exactly this many
+/// bytes makes newer kernels send the same layout.
+const EVENT_SIZE: usize = 8;
+
+/// Combined block state of all Bluetooth radios.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RfkillState {
+    /// Blocked in software, e.g. by airplane mode; can be lifted by the user.
+    pub soft: bool,
+    /// Blocked by a hardware switch or firmware; only the switch can lift it.
+    pub hard: bool,
+}
+
+impl RfkillState {
+    pub fn is_blocked(&self) -> bool {
+        self.soft || self.hard
+    }
+}
+
+/// `struct rfkill_event` from linux/rfkill.h.
+struct Event {
+    idx: u32,
+    kind: u8,
+    op: u8,
+    soft: bool,
+    hard: bool,
+}
+
+impl Event {
+    fn parse(buf: &[u8; EVENT_SIZE]) -> Self {
+        Self {
+            idx: u32::from_ne_bytes([buf[0], buf[1], buf[2], buf[3]]),
+            kind: buf[4],
+            op: buf[5],
+            soft: buf[6] != 0,
+            hard: buf[7] != 0,
+        }
+    }
+}
+
+/// Bluetooth radios by rfkill index, folded from the event stream.
+#[derive(Default)]
+struct Radios(HashMap<u32, (bool, bool)>);
+
+impl Radios {
+    /// Applies `event`, returning whether it concerned a Bluetooth radio.
+    fn apply(&mut self, event: &Event) -> bool {
+        if event.kind != TYPE_BLUETOOTH {
+            return false;
+        }
+        match event.op {
+            OP_ADD | OP_CHANGE => {
+                self.0.insert(event.idx, (event.soft, event.hard));
+            }
+            OP_DEL => {
+                self.0.remove(&event.idx);
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn state(&self) -> RfkillState {
+        RfkillState {
+            soft: self.0.values().any(|(soft, _)| *soft),
+            hard: self.0.values().any(|(_, hard)| *hard),
+        }
+    }
+}
+
+/// Reads and changes rfkill state through an rfkill control device.
+///
+/// Opening the device yields one event per existing radio followed by
+/// change events, so a regular file of events works as a stand-in.
+#[derive(Clone, Debug)]
+pub struct Rfkill {
+    path: PathBuf,
+}
+
+impl Default for Rfkill {
+    fn default() -> Self {
+        Self::new(RFKILL_PATH)
+    }
+}
+
+impl Rfkill {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    fn open(&self, write: bool, nonblocking: bool) -> Result<File> {
+        OpenOptions::new()
+            .read(true)
+            .write(write)
+            .custom_flags(if nonblocking { libc::O_NONBLOCK } else { 0 })
+            .open(&self.path)
+            .with_context(|| format!("Failed to open {}", self.path.display()))
+    }
+
+    /// Current state, without waiting for changes.
+    pub fn state(&self) -> Result<RfkillState> {
+        let mut file = self.open(false, true)?;
+        let mut radios = Radios::default();
+        let mut buf = [0u8; EVENT_SIZE];
+        loop {
+            match file.read_exact(&mut buf) {
+                Ok(()) => {
+                    radios.apply(&Event::parse(&buf));
+                }
+                // Drained the initial events (device) or reached the end (file)
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::UnexpectedEof) => break,
+                Err(e) => return Err(e).with_context(|| format!("Failed to read {}", self.path.display())),
+            }
+        }
+        Ok(radios.state())
+    }
+
+    /// Lifts the soft block on all Bluetooth radios. Hard blocks stay.
+    pub fn unblock(&self) -> Result<()> {
+        let mut file = self.open(true, false)?;
+        let event = [0, 0, 0, 0, TYPE_BLUETOOTH, OP_CHANGE_ALL, 0, 0];
+        file.write_all(&event)
+            .with_context(|| format!("Failed to unblock Bluetooth through {}", self.path.display()))
+    }
+
+    /// Yields the state each time a Bluetooth radio's block changes, appears
+    /// or goes away. A reader thread follows the device until the stream is
+    /// dropped and the next event arrives.
+    pub fn watch(&self) -> Result<BoxStream<'static, RfkillState>> {
+        let mut file = self.open(false, false)?;
+        let (tx, rx) = mpsc::unbounded();
+        thread::Builder::new()
+            .name("rfkill".to_string())
+            .spawn(move || {
+                let mut radios = Radios::default();
+                let mut last = None;
+                let mut buf = [0u8; EVENT_SIZE];
+                while file.read_exact(&mut buf).is_ok() {
+                    if !radios.apply(&Event::parse(&buf)) {
+                        continue;
+                    }
+                    let state = radios.state();
+                    if last != Some(state) {
+                        last = Some(state);
+                        if tx.unbounded_send(state).is_err() {
+                            break;
+                        }
+                    }
+                }
+            })?;
+        Ok(rx.boxed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TYPE_WLAN: u8 = 1;
+
+    fn event(idx: u32, kind: u8, op: u8, soft: bool, hard: bool) -> [u8; EVENT_SIZE] {
+        let idx = idx.to_ne_bytes();
+        [idx[0], idx[1], idx[2], idx[3], kind, op, soft as u8, hard as u8]
+    }
+
+    /// An event file under the temp directory, removed when dropped.
+    struct EventFile(PathBuf);
+
+    impl EventFile {
+        fn new(name: &str, events: &[[u8; EVENT_SIZE]]) -> Self {
+            let path = std::env::temp_dir().join(format!("bluetooth-widget-rfkill-{}-{}", std::process::id(), name));
+            std::fs::write(&path, events.concat()).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for EventFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn parses_events() {
+        let event = Event::parse(&event(258, TYPE_BLUETOOTH, OP_CHANGE, true, false));
+        assert_eq!(event.idx, 258);
+        assert_eq!(event.kind, TYPE_BLUETOOTH);
+        assert_eq!(event.op, OP_CHANGE);
+        assert!(event.soft);
+        assert!(!event.hard);
+    }
+
+    #[test]
+    fn combines_bluetooth_radios() {
+        let mut radios = Radios::default();
+        assert!(radios.apply(&Event::parse(&event(0, TYPE_BLUETOOTH, OP_ADD, true, false))));
+        assert!(radios.apply(&Event::parse(&event(1, TYPE_BLUETOOTH, OP_ADD, false, true))));
+        assert_eq!(radios.state(), RfkillState { soft: true, hard: true });
+
+        assert!(radios.apply(&Event::parse(&event(0, TYPE_BLUETOOTH, OP_CHANGE, false, false))));
+        assert_eq!(radios.state(), RfkillState { soft: false, hard: true });
+
+        assert!(radios.apply(&Event::parse(&event(1, TYPE_BLUETOOTH, OP_DEL, false, true))));
+        assert_eq!(radios.state(), RfkillState::default());
+    }
+
+    #[test]
+    fn ignores_other_radios_and_bulk_changes() {
+        let mut radios = Radios::default();
+        assert!(!radios.apply(&Event::parse(&event(0, TYPE_WLAN, OP_ADD, true, true))));
+        assert!(!radios.apply(&Event::parse(&event(1, TYPE_BLUETOOTH, OP_CHANGE_ALL, true, false))));
+        assert_eq!(radios.state(), RfkillState::default());
+    }
+
+    #[test]
+    fn reads_state_from_an_event_file() {
+        let file = EventFile::new(
+            "state",
+            &[
+                event(0, TYPE_WLAN, OP_ADD, false, true),
+                event(1, TYPE_BLUETOOTH, OP_ADD, false, false),
+                event(1, TYPE_BLUETOOTH, OP_CHANGE, true, false),
+            ],
+        );
+        let state = Rfkill::new(&file.0).state().unwrap();
+        assert_eq!(state, RfkillState { soft: true, hard: false });
+        assert!(state.is_blocked());
+
+        let empty = EventFile::new("empty", &[]);
+        assert_eq!(Rfkill::new(&empty.0).state().unwrap(), RfkillState::default());
+    }
+
+    #[test]
+    fn unblock_writes_a_change_all_event() {
+        let file = EventFile::new("unblock", &[]);
+        Rfkill::new(&file.0).unblock().unwrap();
+        let written = std::fs::read(&file.0).unwrap();
+        assert_eq!(written, [0, 0, 0, 0, TYPE_BLUETOOTH, OP_CHANGE_ALL, 0, 0]);
+    }
+
+    #[test]
+    fn reports_a_missing_device() {
+        let rfkill = Rfkill::new(std::env::temp_dir().join("bluetooth-widget-rfkill-missing"));
+        assert!(rfkill.state().is_err());
+        assert!(rfkill.unblock().is_err());
+    }
+}
@@ -0,0 +1,218 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use anyhow::{Context, Result};
+
+/// A simulated Bluetooth environment, loaded from a JSON scenario file.
+///
+/// ```json
+/// {
+///   "powered": true,
+///   "devices": [
+///     { "address": "00:1A:7D:DA:71:01", "name": "Headphones", "icon": "audio-headset",
+///       "paired": true, "battery": 80, "connect_latency_ms": 1500 },
+///     { "address": "00:1A:7D:DA:71:02", "name": "Old Speaker", "paired": true,
+///       "fail_connect": "Page Timeout" },
+///     { "address": "00:1A:7D:DA:71:03", "name": "Mouse", "discovery_delay_ms": 3000 }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct Scenario {
+    pub powered: bool,
+    /// Delay applied to power changes.
+    pub power_latency_ms: u64,
+    pub devices: Vec<ScenarioDevice>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ScenarioDevice {
+    pub address: String,
+    /// Remote name the device advertises.
+    pub name: String,
+    /// Alias already set by the user.
+    #[serde(default)]
+    pub alias: Option<String>,
+    #[serde(default = "default_icon")]
+    pub icon: String,
+    #[serde(default)]
+    pub paired: bool,
+    #[serde(default)]
+    pub connected: bool,
+    #[serde(default)]
+    pub trusted: bool,
+    #[serde(default)]
+    pub blocked: bool,
+    #[serde(default)]
+    pub battery: Option<u8>,
+    /// While connected, the battery drops by 1% this often.
+    #[serde(default)]
+    pub battery_drain_ms: Option<u64>,
+    #[serde(default)]
+    pub connect_latency_ms: u64,
+    #[serde(default)]
+    pub pair_latency_ms: u64,
+    /// When set, connecting fails with this message after the latency elapses.
+    #[serde(default)]
+    pub fail_connect: Option<String>,
+    /// When set, pairing fails with this message after the latency elapses.
+    #[serde(default)]
+    pub fail_pair: Option<String>,
+    /// When set, the device is out of range until a scan has run this long.
+    #[serde(default)]
+    pub discovery_delay_ms: Option<u64>,
+    /// Agent prompt raised while pairing; without one pairing is "just works".
+    #[serde(default)]
+    pub pairing: Option<ScenarioPairing>,
+    /// Service UUIDs for the details view, full or short (`"110b"`).
+    #[serde(default)]
+    pub uuids: Vec<String>,
+    /// Class of device for the details view, e.g. `2360324` (0x240404, headset).
+    #[serde(default)]
+    pub class: Option<u32>,
+    #[serde(default)]
+    pub rssi: Option<i16>,
+}
+
+/// How a simulated device authenticates, e.g. `{ "method": "confirmation", "passkey": 123456 }`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum ScenarioPairing {
+    /// The user must enter `expected`.
+    PinCode { expected: String },
+    /// The user must enter `expected`.
+    Passkey { expected: u32 },
+    /// The user types `passkey` on the device; typing takes `typing_ms`.
+    DisplayPasskey {
+        passkey: u32,
+        #[serde(default = "default_typing_ms")]
+        typing_ms: u64,
+    },
+    /// The user confirms that both sides show `passkey`.
+    Confirmation { passkey: u32 },
+}
+
+fn default_typing_ms() -> u64 {
+    3000
+}
+
+fn default_icon() -> String {
+    "bluetooth".to_string()
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read scenario {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse scenario {}", path.display()))
+    }
+}
+
+impl Default for Scenario {
+    /// A small demo environment used when no scenario file is configured.
+    fn default() -> Self {
+        let device = |address: &str, name: &str, icon: &str| ScenarioDevice {
+            address: address.to_string(),
+            name: name.to_string(),
+            alias: None,
+            icon: icon.to_string(),
+            paired: true,
+            connected: false,
+            trusted: true,
+            blocked: false,
+            battery: None,
+            battery_drain_ms: None,
+            connect_latency_ms: 800,
+            pair_latency_ms: 1500,
+            fail_connect: None,
+            fail_pair: None,
+            discovery_delay_ms: None,
+            pairing: None,
+            uuids: Vec::new(),
+            class: None,
+            rssi: None,
+        };
+
+        Self {
+            powered: true,
+            power_latency_ms: 300,
+            devices: vec![
+                ScenarioDevice {
+                    connected: true,
+                    battery: Some(72),
+                    battery_drain_ms: Some(20_000),
+                    ..device("00:1A:7D:DA:71:01", "Headphones", "audio-headset")
+                },
+                ScenarioDevice {
+                    battery: Some(35),
+                    ..device("00:1A:7D:DA:71:02", "Keyboard", "input-keyboard")
+                },
+                ScenarioDevice {
+                    fail_connect: Some("Page Timeout".to_string()),
+                    ..device("00:1A:7D:DA:71:03", "Old Speaker", "audio-card")
+                },
+                ScenarioDevice {
+                    paired: false,
+                    pairing: Some(ScenarioPairing::Confirmation { passkey: 482913 }),
+                    ..device("00:1A:7D:DA:71:04", "Phone", "phone")
+                },
+                ScenarioDevice {
+                    paired: false,
+                    discovery_delay_ms: Some(2500),
+                    ..device("00:1A:7D:DA:71:05", "Living Room TV", "video-display")
+                },
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_the_demo_scenario() {
+        let scenario = Scenario::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios/demo.json")).unwrap();
+        assert!(scenario.powered);
+        assert_eq!(scenario.power_latency_ms, 300);
+        assert_eq!(scenario.devices.len(), 6);
+
+        let headphones = &scenario.devices[0];
+        assert_eq!(headphones.alias.as_deref(), Some("Office Headphones"));
+        assert_eq!(headphones.battery_drain_ms, Some(10_000));
+        assert_eq!(scenario.devices[2].fail_connect.as_deref(), Some("Page Timeout"));
+        assert_eq!(scenario.devices[3].fail_pair.as_deref(), Some("Authentication Rejected"));
+        assert!(matches!(scenario.devices[4].pairing, Some(ScenarioPairing::Passkey { expected: 123456 })));
+        assert!(matches!(
+            scenario.devices[5].pairing,
+            Some(ScenarioPairing::DisplayPasskey { passkey: 318024, typing_ms: 4000 })
+        ));
+    }
+
+    #[test]
+    fn fills_in_defaults() {
+        let scenario: Scenario =
+            serde_json::from_str(r#"{ "devices": [{ "address": "00:1A:7D:DA:71:01", "name": "Mouse" }] }"#).unwrap();
+        assert!(scenario.powered);
+        let mouse = &scenario.devices[0];
+        assert_eq!(mouse.icon, "bluetooth");
+        assert!(!mouse.paired && !mouse.connected);
+        assert_eq!(mouse.connect_latency_ms, 0);
+        assert!(mouse.discovery_delay_ms.is_none());
+
+        let pairing: ScenarioPairing =
+            serde_json::from_str(r#"{ "method": "display_passkey", "passkey": 1 }"#).unwrap();
+        assert!(matches!(pairing, ScenarioPairing::DisplayPasskey { typing_ms: 3000, .. }));
+    }
+
+    #[test]
+    fn reports_the_file_it_could_not_parse() {
+        let path = std::env::temp_dir().join(format!("bluetooth-widget-scenario-{}.json", std::process::id()));
+        fs::write(&path, "{ \"devices\": 3 }").unwrap();
+        let error = Scenario::load(&path).unwrap_err();
+        let _ = fs::remove_file(&path);
+        assert!(format!("{}", error).contains(&path.display().to_string()));
+    }
+}
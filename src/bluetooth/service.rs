@@ -0,0 +1,504 @@
+use bluer::Address;
+use futures::channel::{mpsc, oneshot};
+use futures::future;
+use futures::stream::{self, BoxStream, StreamExt};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use tokio::sync::watch;
+use anyhow::{anyhow, Result};
+
+use crate::config::{BackendKind, Config};
+
+use super::bus;
+use super::{
+    AdapterInfo, BluetoothBackend, BluetoothDevice, BluetoothError, BluetoothEvent, BluezBackend,
+    DeviceDetails, FakeBackend, PairingRequest, Rfkill, RfkillState, Scenario,
+};
+
+type Reply<T> = oneshot::Sender<Result<T>>;
+
+/// How long powering on waits for bluetoothd to bring the adapter back after
+/// lifting an rfkill block, before asking for power itself.
+const UNBLOCK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Whether the service currently has a working backend.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ServiceState {
+    /// Creating the backend, at startup or on a retry.
+    Connecting,
+    Ready,
+    /// Creating the backend failed, e.g. because bluetoothd isn't running.
+    /// Commands fail with this reason until a retry succeeds.
+    Unavailable(String),
+}
+
+/// Requests handled by the service actor.
+enum Command {
+    Adapters(Reply<Vec<AdapterInfo>>),
+    CurrentAdapter(Reply<Option<String>>),
+    SelectAdapter(String, Reply<()>),
+    IsPowered(Reply<bool>),
+    SetPowered(bool, Reply<()>),
+    /// Lifts a software rfkill block; see `unblock`.
+    Unblock(Rfkill, Reply<()>),
+    GetDevices(Reply<Vec<BluetoothDevice>>),
+    Connect(Address, Reply<()>),
+    Disconnect(Address, Reply<()>),
+    Pair(Address, Reply<()>),
+    SetAlias(Address, String, Reply<()>),
+    Details(Address, Reply<DeviceDetails>),
+    Remove(Address, Reply<()>),
+    SetTrusted(Address, bool, Reply<()>),
+    SetBlocked(Address, bool, Reply<()>),
+    Subscribe(Reply<BoxStream<'static, BluetoothEvent>>),
+    Discover(Reply<BoxStream<'static, BluetoothEvent>>),
+    PairingRequests(Reply<BoxStream<'static, PairingRequest>>),
+    /// Recreates the backend if it is unavailable.
+    Retry(Reply<()>),
+}
+
+impl Command {
+    async fn execute(self, backend: &dyn BluetoothBackend) {
+        // A dropped receiver just means the caller stopped waiting.
+        match self {
+            Command::Adapters(reply) => {
+                let _ = reply.send(backend.adapters().await);
+            }
+            Command::CurrentAdapter(reply) => {
+                let _ = reply.send(backend.current_adapter().await);
+            }
+            Command::SelectAdapter(name, reply) => {
+                let _ = reply.send(backend.select_adapter(name).await);
+            }
+            Command::IsPowered(reply) => {
+                let _ = reply.send(backend.is_powered().await);
+            }
+            Command::SetPowered(powered, reply) => {
+                let _ = reply.send(backend.set_powered(powered).await);
+            }
+            Command::Unblock(rfkill, reply) => {
+                let _ = reply.send(unblock(backend, &rfkill).await);
+            }
+            Command::GetDevices(reply) => {
+                let _ = reply.send(backend.get_devices().await);
+            }
+            Command::Connect(address, reply) => {
+                let _ = reply.send(backend.connect_device(address).await);
+            }
+            Command::Disconnect(address, reply) => {
+                let _ = reply.send(backend.disconnect_device(address).await);
+            }
+            Command::Pair(address, reply) => {
+                let _ = reply.send(backend.pair_device(address).await);
+            }
+            Command::SetAlias(address, alias, reply) => {
+                let _ = reply.send(backend.set_alias(address, alias).await);
+            }
+            Command::Details(address, reply) => {
+                let _ = reply.send(backend.device_details(address).await);
+            }
+            Command::Remove(address, reply) => {
+                let _ = reply.send(backend.remove_device(address).await);
+            }
+            Command::SetTrusted(address, trusted, reply) => {
+                let _ = reply.send(backend.set_trusted(address, trusted).await);
+            }
+            Command::SetBlocked(address, blocked, reply) => {
+                let _ = reply.send(backend.set_blocked(address, blocked).await);
+            }
+            Command::Subscribe(reply) => {
+                let _ = reply.send(backend.subscribe().await);
+            }
+            Command::Discover(reply) => {
+                let _ = reply.send(backend.discover().await);
+            }
+            Command::PairingRequests(reply) => {
+                let _ = reply.send(backend.pairing_requests().await);
+            }
+            Command::Retry(reply) => {
+                let _ = reply.send(Ok(()));
+            }
+        }
+    }
+
+    /// Fails the command without a backend to run it on.
+    fn reject(self, reason: &str) {
+        fn fail<T>(reply: Reply<T>, reason: &str) {
+            let error = BluetoothError::NotReady(format!("Bluetooth is unavailable: {}", reason));
+            let _ = reply.send(Err(error.into()));
+        }
+
+        match self {
+            Command::Adapters(reply) => fail(reply, reason),
+            Command::CurrentAdapter(reply) => fail(reply, reason),
+            Command::SelectAdapter(_, reply) => fail(reply, reason),
+            Command::IsPowered(reply) => fail(reply, reason),
+            Command::SetPowered(_, reply) => fail(reply, reason),
+            Command::Unblock(_, reply) => fail(reply, reason),
+            Command::GetDevices(reply) => fail(reply, reason),
+            Command::Connect(_, reply) => fail(reply, reason),
+            Command::Disconnect(_, reply) => fail(reply, reason),
+            Command::Pair(_, reply) => fail(reply, reason),
+            Command::SetAlias(_, _, reply) => fail(reply, reason),
+            Command::Details(_, reply) => fail(reply, reason),
+            Command::Remove(_, reply) => fail(reply, reason),
+            Command::SetTrusted(_, _, reply) => fail(reply, reason),
+            Command::SetBlocked(_, _, reply) => fail(reply, reason),
+            Command::Subscribe(reply) => fail(reply, reason),
+            Command::Discover(reply) => fail(reply, reason),
+            Command::PairingRequests(reply) => fail(reply, reason),
+            Command::Retry(reply) => fail(reply, reason),
+        }
+    }
+}
+
+/// Lifts a software rfkill block, then waits up to `UNBLOCK_TIMEOUT` for
+/// bluetoothd to power the adapter back up, which it does once it sees the
+/// radio return; it refuses power changes before then.
+async fn unblock(backend: &dyn BluetoothBackend, rfkill: &Rfkill) -> Result<()> {
+    // Subscribed first so the power change can't slip past
+    let events = backend.subscribe().await;
+    rfkill
+        .unblock()
+        .map_err(|e| BluetoothError::Rfkill(format!("Could not unblock Bluetooth: {:#}", e)))?;
+    if let Ok(events) = events {
+        let mut powered = events.filter(|event| future::ready(matches!(event, BluetoothEvent::PowerChanged(true))));
+        let _ = tokio::time::timeout(UNBLOCK_TIMEOUT, powered.next()).await;
+    }
+    Ok(())
+}
+
+/// Builds the backend and publishes the outcome to `state`.
+async fn connect<F, Fut>(make_backend: &F, state: &watch::Sender<ServiceState>) -> Option<Arc<dyn BluetoothBackend>>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<Box<dyn BluetoothBackend>>>,
+{
+    state.send_replace(ServiceState::Connecting);
+    match make_backend().await {
+        Ok(backend) => {
+            state.send_replace(ServiceState::Ready);
+            Some(backend.into())
+        }
+        Err(e) => {
+            eprintln!("Bluetooth backend unavailable: {:#}", e);
+            state.send_replace(ServiceState::Unavailable(format!("{:#}", e)));
+            None
+        }
+    }
+}
+
+/// Handle to the Bluetooth actor.
+///
+/// The backend lives on a dedicated thread with its own tokio runtime; every
+/// method sends a command and returns a future for the reply, so callers on
+/// the GTK main context never block. Cloning is cheap and all clones talk to
+/// the same actor, which shuts down when the last handle is dropped.
+#[derive(Clone)]
+pub struct BluetoothService {
+    commands: mpsc::UnboundedSender<Command>,
+    state: watch::Receiver<ServiceState>,
+    /// Last adapter selected through this service, reused when the backend
+    /// is rebuilt after a bluetoothd restart.
+    adapter: Arc<Mutex<Option<String>>>,
+    /// Radio blocks are read directly, so they are known even without bluetoothd.
+    rfkill: Rfkill,
+}
+
+impl BluetoothService {
+    /// Connects to bluetoothd, using `adapter` if it is present and the
+    /// default adapter otherwise, and registers the pairing agent if `agent`
+    /// is set. With `follow_bluez`, a service started before bluetoothd
+    /// starts unavailable and connects once `org.bluez` appears on the bus.
+    pub fn new(adapter: Option<String>, agent: bool, follow_bluez: bool) -> Self {
+        let adapter = Arc::new(Mutex::new(adapter));
+        let preferred = adapter.clone();
+        let make_backend = move || {
+            let adapter = preferred.lock().ok().and_then(|adapter| adapter.clone());
+            async move {
+                let backend = BluezBackend::new(adapter, agent).await?;
+                Ok(Box::new(backend) as Box<dyn BluetoothBackend>)
+            }
+        };
+        Self {
+            adapter,
+            ..Self::spawn(make_backend, follow_bluez)
+        }
+    }
+
+    /// Creates the service for the backend selected in `config`, with the
+    /// pairing agent and following bluetoothd restarts.
+    pub fn from_config(config: &Config) -> Self {
+        Self::for_config(config, true, true)
+    }
+
+    /// Like `from_config`, but without a pairing agent, for commands that
+    /// never pair. Only commands that keep running need `follow_bluez`.
+    pub fn headless(config: &Config, follow_bluez: bool) -> Self {
+        Self::for_config(config, false, follow_bluez)
+    }
+
+    fn for_config(config: &Config, agent: bool, follow_bluez: bool) -> Self {
+        let service = match config.backend {
+            BackendKind::Bluez => Self::new(config.adapter.clone(), agent, follow_bluez),
+            BackendKind::Simulation => {
+                // Reloaded on every retry, so a fixed scenario file is picked up.
+                let path = config.simulation_scenario.clone();
+                let make_backend = move || {
+                    let path = path.clone();
+                    async move {
+                        let scenario = match path {
+                            Some(path) => Scenario::load(&path)?,
+                            None => Scenario::default(),
+                        };
+                        Ok(Box::new(FakeBackend::from_scenario(scenario)?) as Box<dyn BluetoothBackend>)
+                    }
+                };
+                Self::spawn(make_backend, false)
+            }
+        };
+        Self {
+            rfkill: Rfkill::new(&config.rfkill_path),
+            ..service
+        }
+    }
+
+    /// Starts the actor thread. The backend is built there, and rebuilt by
+    /// `retry` or, with `follow_bluez`, whenever bluetoothd (re)starts; it is
+    /// dropped when bluetoothd exits. Commands sent while building queue up
+    /// behind the attempt.
+    fn spawn<F, Fut>(make_backend: F, follow_bluez: bool) -> Self
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<Box<dyn BluetoothBackend>>>,
+    {
+        let (commands, mut receiver) = mpsc::unbounded::<Command>();
+        let (state_tx, state) = watch::channel(ServiceState::Connecting);
+
+        let started = thread::Builder::new()
+            .name("bluetooth-service".to_string())
+            .spawn(move || {
+                let rt = match Runtime::new() {
+                    Ok(rt) => rt,
+                    Err(e) => {
+                        state_tx.send_replace(ServiceState::Unavailable(e.to_string()));
+                        return;
+                    }
+                };
+
+                rt.block_on(async move {
+                    let mut bluez = match follow_bluez {
+                        true => bus::watch_bluez().await.unwrap_or_else(|e| {
+                            eprintln!("Not watching for bluetoothd: {:#}", e);
+                            stream::pending().boxed()
+                        }),
+                        false => stream::pending().boxed(),
+                    };
+                    let mut backend = connect(&make_backend, &state_tx).await;
+
+                    loop {
+                        tokio::select! {
+                            command = receiver.next() => match (command, backend.clone()) {
+                                (Some(Command::Retry(reply)), None) => {
+                                    backend = connect(&make_backend, &state_tx).await;
+                                    match &*state_tx.borrow() {
+                                        ServiceState::Unavailable(reason) => Command::Retry(reply).reject(reason),
+                                        _ => {
+                                            let _ = reply.send(Ok(()));
+                                        }
+                                    }
+                                }
+                                // Each command runs as its own task, so a slow connect
+                                // does not hold up device listing or power changes.
+                                (Some(command), Some(backend)) => {
+                                    tokio::spawn(async move { command.execute(&*backend).await });
+                                }
+                                (Some(command), None) => match &*state_tx.borrow() {
+                                    ServiceState::Unavailable(reason) => command.reject(reason),
+                                    _ => command.reject("not connected"),
+                                },
+                                (None, _) => break,
+                            },
+                            // A new owner means a fresh bluetoothd: its objects, our
+                            // agent and any signal matches from the old one are gone,
+                            // so always rebuild rather than reuse the old session.
+                            Some(present) = bluez.next() => {
+                                if present {
+                                    backend = connect(&make_backend, &state_tx).await;
+                                } else if backend.take().is_some() {
+                                    state_tx.send_replace(ServiceState::Unavailable(
+                                        "bluetoothd stopped; waiting for it to restart".to_string(),
+                                    ));
+                                }
+                            }
+                        }
+                    }
+                });
+            });
+
+        let state = match started {
+            Ok(_) => state,
+            Err(e) => {
+                let reason = format!("Failed to start the Bluetooth service: {}", e);
+                watch::channel(ServiceState::Unavailable(reason)).1
+            }
+        };
+
+        Self {
+            commands,
+            state,
+            adapter: Arc::default(),
+            rfkill: Rfkill::default(),
+        }
+    }
+
+    /// The current state; see `watch_state` to follow changes.
+    pub fn state(&self) -> ServiceState {
+        self.state.borrow().clone()
+    }
+
+    /// Yields the current state, then every change.
+    pub fn watch_state(&self) -> BoxStream<'static, ServiceState> {
+        let mut state = self.state.clone();
+        let current = state.borrow_and_update().clone();
+        let changes = stream::unfold(state, |mut state| async move {
+            state.changed().await.ok()?;
+            let current = state.borrow_and_update().clone();
+            Some((current, state))
+        });
+        stream::once(async move { current }).chain(changes).boxed()
+    }
+
+    /// Block state of the Bluetooth radios; reported as unblocked if rfkill
+    /// can't be read.
+    pub fn rfkill_state(&self) -> RfkillState {
+        self.rfkill.state().unwrap_or_else(|e| {
+            eprintln!("Failed to read rfkill state: {:#}", e);
+            RfkillState::default()
+        })
+    }
+
+    pub fn watch_rfkill(&self) -> Result<BoxStream<'static, RfkillState>> {
+        self.rfkill.watch()
+    }
+
+    /// Lifts a software block (e.g. airplane mode) on Bluetooth.
+    pub fn unblock_rfkill(&self) -> Result<()> {
+        self.rfkill.unblock()
+    }
+
+    /// Tries to recreate an unavailable backend; succeeds straight away if
+    /// the backend is already up.
+    pub async fn retry(&self) -> Result<()> {
+        self.request(Command::Retry).await
+    }
+
+    async fn request<T>(&self, command: impl FnOnce(Reply<T>) -> Command) -> Result<T> {
+        let (reply, response) = oneshot::channel();
+        self.commands
+            .unbounded_send(command(reply))
+            .map_err(|_| anyhow!("Bluetooth service has stopped"))?;
+        response
+            .await
+            .map_err(|_| anyhow!("Bluetooth service dropped the request"))?
+    }
+
+    pub async fn adapters(&self) -> Result<Vec<AdapterInfo>, BluetoothError> {
+        Ok(self.request(Command::Adapters).await?)
+    }
+
+    /// The selected adapter; `None` if there is none, e.g. after it was unplugged.
+    pub async fn current_adapter(&self) -> Result<Option<String>, BluetoothError> {
+        Ok(self.request(Command::CurrentAdapter).await?)
+    }
+
+    pub async fn select_adapter(&self, name: String) -> Result<()> {
+        self.request(|reply| Command::SelectAdapter(name.clone(), reply)).await?;
+        if let Ok(mut adapter) = self.adapter.lock() {
+            *adapter = Some(name);
+        }
+        Ok(())
+    }
+
+    pub async fn is_powered(&self) -> Result<bool, BluetoothError> {
+        Ok(self.request(Command::IsPowered).await?)
+    }
+
+    /// Turns the adapter on or off. Turning it on lifts a software rfkill
+    /// block first; a hardware block fails with `BluetoothError::Rfkill`.
+    pub async fn set_powered(&self, on: bool) -> Result<(), BluetoothError> {
+        if !on {
+            return self.power_off().await;
+        }
+        let rfkill = self.rfkill_state();
+        if rfkill.hard {
+            return Err(BluetoothError::Rfkill("Bluetooth is blocked by a hardware switch".to_string()));
+        }
+        if rfkill.soft {
+            let control = self.rfkill.clone();
+            self.request(|reply| Command::Unblock(control, reply)).await?;
+        }
+        self.power_on().await
+    }
+
+    pub async fn power_on(&self) -> Result<(), BluetoothError> {
+        Ok(self.request(|reply| Command::SetPowered(true, reply)).await?)
+    }
+
+    pub async fn power_off(&self) -> Result<(), BluetoothError> {
+        Ok(self.request(|reply| Command::SetPowered(false, reply)).await?)
+    }
+
+    pub async fn get_devices(&self) -> Result<Vec<BluetoothDevice>, BluetoothError> {
+        Ok(self.request(Command::GetDevices).await?)
+    }
+
+    pub async fn connect_device(&self, address: Address) -> Result<(), BluetoothError> {
+        Ok(self.request(|reply| Command::Connect(address, reply)).await?)
+    }
+
+    pub async fn disconnect_device(&self, address: Address) -> Result<(), BluetoothError> {
+        Ok(self.request(|reply| Command::Disconnect(address, reply)).await?)
+    }
+
+    pub async fn pair_device(&self, address: Address) -> Result<(), BluetoothError> {
+        Ok(self.request(|reply| Command::Pair(address, reply)).await?)
+    }
+
+    /// Renames the device locally; an empty name restores the remote name.
+    pub async fn rename_device(&self, address: Address, alias: String) -> Result<(), BluetoothError> {
+        Ok(self.request(move |reply| Command::SetAlias(address, alias, reply)).await?)
+    }
+
+    pub async fn device_details(&self, address: Address) -> Result<DeviceDetails, BluetoothError> {
+        Ok(self.request(|reply| Command::Details(address, reply)).await?)
+    }
+
+    pub async fn remove_device(&self, address: Address) -> Result<(), BluetoothError> {
+        Ok(self.request(|reply| Command::Remove(address, reply)).await?)
+    }
+
+    pub async fn set_trusted(&self, address: Address, trusted: bool) -> Result<(), BluetoothError> {
+        Ok(self.request(|reply| Command::SetTrusted(address, trusted, reply)).await?)
+    }
+
+    pub async fn set_blocked(&self, address: Address, blocked: bool) -> Result<(), BluetoothError> {
+        Ok(self.request(|reply| Command::SetBlocked(address, blocked, reply)).await?)
+    }
+
+    pub async fn subscribe(&self) -> Result<BoxStream<'static, BluetoothEvent>> {
+        self.request(Command::Subscribe).await
+    }
+
+    /// Starts discovery; it runs until the returned stream is dropped.
+    pub async fn discover(&self) -> Result<BoxStream<'static, BluetoothEvent>> {
+        self.request(Command::Discover).await
+    }
+
+    pub async fn pairing_requests(&self) -> Result<BoxStream<'static, PairingRequest>> {
+        self.request(Command::PairingRequests).await
+    }
+}
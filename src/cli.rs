@@ -0,0 +1,727 @@
+use bluer::Address;
+use futures::executor::block_on;
+use futures::stream::{self, BoxStream};
+use futures::{future, StreamExt};
+use std::io::{self, BufRead, Write};
+use std::process::ExitCode;
+
+use crate::bar;
+use crate::bluetooth::{
+    compare_devices, BluetoothDevice, BluetoothError, BluetoothEvent, BluetoothService, PairingRequest, RfkillState,
+    ServiceState,
+};
+use crate::config::Config;
+use crate::json;
+use crate::privacy;
+
+const USAGE: &str = "\
+Usage: bluetooth-widget [--json] [COMMAND]
+
+Without a command the widget window opens, or is hidden if it is already open.
+
+Window commands, handled by the running widget if there is one:
+  toggle                Open or close the window (the default)
+  show                  Open the window, or raise it if it is open
+  hide                  Hide the window
+  background            Keep running without a window, for the D-Bus API
+
+Commands:
+  list                  List known devices
+  status                Show adapter and power state
+  watch                 Print the status, then every change until interrupted
+  bar [OPTIONS]         Keep printing a status line for a desktop bar
+  connect <DEVICE>      Connect a paired device
+  disconnect <DEVICE>   Disconnect a device
+  pair <DEVICE>         Pair a device found by a scan
+  power on|off|toggle   Switch the adapter on or off
+  help                  Show this message
+
+DEVICE is an address or a device name (alias), matched case-insensitively.
+
+Bar options:
+  --waybar              Print Waybar JSON with a tooltip and classes
+  --once                Print one line and exit
+  --format <FORMAT>     Override bar_format, e.g. '{name} {battery}'
+
+With --json, output is JSON on stdout: one document per command, or one
+event per line for watch. Failures are reported as JSON too, with the same
+exit status. Pairing prompts always go to stderr.
+
+Exit status: 0 on success, 1 if the operation failed, 2 on a usage error,
+3 if Bluetooth is unavailable, 4 if the device is unknown or ambiguous.";
+
+/// Exit statuses, so scripts can tell failures apart.
+mod status {
+    pub const FAILED: u8 = 1;
+    pub const USAGE: u8 = 2;
+    pub const UNAVAILABLE: u8 = 3;
+    pub const NO_DEVICE: u8 = 4;
+}
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Watch,
+    Bar(bar::Options),
+    Help,
+    /// Runs once Bluetooth is ready, then exits.
+    Once(Action),
+}
+
+#[derive(Debug, PartialEq)]
+enum Action {
+    List,
+    Status,
+    Connect(String),
+    Disconnect(String),
+    Pair(String),
+    Power(Power),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Power {
+    On,
+    Off,
+    Toggle,
+}
+
+/// A failed command: what went wrong and the exit status.
+struct Failure {
+    /// Stable identifier for `--json`: a `BluetoothError::kind`, or one of
+    /// `usage`, `unavailable`, `no-device` and `rfkill`.
+    kind: &'static str,
+    message: String,
+    action: Option<&'static str>,
+    code: u8,
+}
+
+impl Failure {
+    fn new(kind: &'static str, message: impl Into<String>, code: u8) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            action: None,
+            code,
+        }
+    }
+
+    fn unavailable(message: impl Into<String>) -> Self {
+        Self::new("unavailable", message, status::UNAVAILABLE)
+    }
+
+    fn no_device(message: impl Into<String>) -> Self {
+        Self::new("no-device", message, status::NO_DEVICE)
+    }
+
+    /// Reports the failure on stderr, or on stdout as JSON, and returns the exit status.
+    fn report(self, json: bool) -> ExitCode {
+        if json {
+            println!(
+                "{}",
+                json::line(json::Outcome {
+                    ok: false,
+                    error: Some(json::Error {
+                        kind: self.kind.to_string(),
+                        message: privacy::mask_text(&self.message),
+                        action: self.action.map(str::to_string),
+                    }),
+                    device: None,
+                    powered: None,
+                })
+            );
+        } else {
+            let message = match self.action {
+                Some(action) => format!("{}. {}", self.message, action),
+                None => self.message,
+            };
+            eprintln!("bluetooth-widget: {}", privacy::mask_text(&message));
+        }
+        ExitCode::from(self.code)
+    }
+}
+
+impl From<BluetoothError> for Failure {
+    fn from(error: BluetoothError) -> Self {
+        let code = match error {
+            BluetoothError::NotReady(_) => status::UNAVAILABLE,
+            _ => status::FAILED,
+        };
+        Failure {
+            kind: error.kind(),
+            message: error.message().to_string(),
+            action: error.action(),
+            code,
+        }
+    }
+}
+
+fn parse(args: &[String]) -> Result<Command, String> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["list"] => Ok(Command::Once(Action::List)),
+        ["status"] => Ok(Command::Once(Action::Status)),
+        ["watch"] => Ok(Command::Watch),
+        ["bar", options @ ..] => parse_bar(options).map(Command::Bar),
+        ["connect", device] => Ok(Command::Once(Action::Connect(device.to_string()))),
+        ["disconnect", device] => Ok(Command::Once(Action::Disconnect(device.to_string()))),
+        ["pair", device] => Ok(Command::Once(Action::Pair(device.to_string()))),
+        ["power", "on"] => Ok(Command::Once(Action::Power(Power::On))),
+        ["power", "off"] => Ok(Command::Once(Action::Power(Power::Off))),
+        ["power", "toggle"] => Ok(Command::Once(Action::Power(Power::Toggle))),
+        ["help" | "--help" | "-h"] => Ok(Command::Help),
+        ["connect" | "disconnect" | "pair"] => Err(format!("{} needs a device address or name", args[0])),
+        ["list" | "status" | "watch" | "help", ..] => Err(format!("{} takes no arguments", args[0])),
+        ["power", ..] => Err("power takes on, off or toggle".to_string()),
+        [command, ..] => Err(format!("unknown command '{}'", command)),
+        [] => Err("no command given".to_string()),
+    }
+}
+
+fn parse_bar(args: &[&str]) -> Result<bar::Options, String> {
+    let mut options = bar::Options::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--waybar" => options.output = bar::Output::Waybar,
+            "--once" => options.once = true,
+            "--format" => match args.next() {
+                Some(format) => options.format = Some(format.to_string()),
+                None => return Err("--format needs a format string".to_string()),
+            },
+            other => return Err(format!("unknown bar option '{}'", other)),
+        }
+    }
+    Ok(options)
+}
+
+/// Runs one command without a display and returns the exit status.
+pub fn run(args: &[String]) -> ExitCode {
+    let (json, args) = take_json(args);
+
+    let command = match parse(&args) {
+        Ok(command) => command,
+        Err(message) if json => return Failure::new("usage", message, status::USAGE).report(true),
+        Err(message) => {
+            eprintln!("bluetooth-widget: {}\n\n{}", message, USAGE);
+            return ExitCode::from(status::USAGE);
+        }
+    };
+
+    let result = match command {
+        Command::Help => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Command::Bar(_) if json => Err(Failure::new("usage", "bar takes --waybar for JSON output", status::USAGE)),
+        // The commands that keep running reconnect when bluetoothd restarts
+        Command::Watch => {
+            let config = load_config();
+            block_on(watch(&BluetoothService::headless(&config, true), &config, json))
+        }
+        Command::Bar(options) => {
+            let config = load_config();
+            block_on(run_bar(&BluetoothService::headless(&config, true), &config, &options))
+        }
+        Command::Once(action) => {
+            let config = load_config();
+            // Only pairing needs an agent to answer its prompts
+            let service = match action {
+                Action::Pair(_) => BluetoothService::from_config(&config),
+                _ => BluetoothService::headless(&config, false),
+            };
+            block_on(execute(&service, &config, action, json))
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => failure.report(json),
+    }
+}
+
+/// Removes `--json` from `args`. It may appear anywhere, so
+/// `bluetooth-widget list --json` works too.
+fn take_json(args: &[String]) -> (bool, Vec<String>) {
+    let json = args.iter().any(|arg| arg == "--json");
+    (json, args.iter().filter(|arg| *arg != "--json").cloned().collect())
+}
+
+/// Loads the config and applies its address mask to everything printed.
+fn load_config() -> Config {
+    let config = Config::load();
+    privacy::set_mask(config.address_mask);
+    config
+}
+
+async fn execute(service: &BluetoothService, config: &Config, action: Action, json: bool) -> Result<(), Failure> {
+    wait_until_ready(service).await?;
+
+    match action {
+        Action::List => {
+            let devices = sorted_devices(service, config).await?;
+            if json {
+                let devices = devices.iter().map(json::Device::from).collect();
+                println!("{}", json::line(json::DeviceList { devices }));
+            } else {
+                for device in devices {
+                    println!("{}", list_line(&device));
+                }
+            }
+        }
+        Action::Status if json => println!("{}", json::line(status(service, config).await?)),
+        Action::Status => print_status(service, config).await?,
+        Action::Connect(query) => {
+            let device = find_device(service, &query).await?;
+            match service.connect_device(device.address).await {
+                Ok(()) | Err(BluetoothError::AlreadyConnected) => done(json, &device, "Connected"),
+                Err(e) => return Err(failed("connect", &device, e)),
+            }
+        }
+        Action::Disconnect(query) => {
+            let device = find_device(service, &query).await?;
+            service.disconnect_device(device.address).await.map_err(|e| failed("disconnect", &device, e))?;
+            done(json, &device, "Disconnected");
+        }
+        Action::Pair(query) => {
+            let device = find_device(service, &query).await?;
+            pair(service, &device).await.map_err(|e| failed("pair", &device, e))?;
+            done(json, &device, "Paired");
+        }
+        Action::Power(power) => {
+            let on = match power {
+                Power::On => true,
+                Power::Off => false,
+                Power::Toggle => !service.is_powered().await?,
+            };
+            service.set_powered(on).await?;
+            let powered = service.is_powered().await?;
+            if json {
+                println!(
+                    "{}",
+                    json::line(json::Outcome {
+                        ok: true,
+                        error: None,
+                        device: None,
+                        powered: Some(powered),
+                    })
+                );
+            } else {
+                println!("Bluetooth is {}", if powered { "on" } else { "off" });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reports a successful device action.
+fn done(json: bool, device: &BluetoothDevice, past_tense: &str) {
+    if json {
+        println!(
+            "{}",
+            json::line(json::Outcome {
+                ok: true,
+                error: None,
+                device: Some(device.into()),
+                powered: None,
+            })
+        );
+    } else {
+        println!("{} {}", past_tense, privacy::mask_name(&device.name, &device.address));
+    }
+}
+
+fn failed(verb: &str, device: &BluetoothDevice, error: BluetoothError) -> Failure {
+    let failure = Failure::from(error);
+    Failure {
+        message: format!(
+            "could not {} {}: {}",
+            verb,
+            privacy::mask_name(&device.name, &device.address),
+            failure.message
+        ),
+        ..failure
+    }
+}
+
+/// Waits out the initial connection attempt to bluetoothd.
+async fn wait_until_ready(service: &BluetoothService) -> Result<(), Failure> {
+    let mut states = service.watch_state();
+    while let Some(state) = states.next().await {
+        match state {
+            ServiceState::Connecting => continue,
+            ServiceState::Ready => return Ok(()),
+            ServiceState::Unavailable(reason) => {
+                return Err(Failure::unavailable(format!("Bluetooth is unavailable: {}", reason)))
+            }
+        }
+    }
+    Err(Failure::unavailable("Bluetooth service stopped"))
+}
+
+/// Devices in the same order as the widget shows them.
+async fn sorted_devices(service: &BluetoothService, config: &Config) -> Result<Vec<BluetoothDevice>, BluetoothError> {
+    let mut devices = service.get_devices().await?;
+    devices.sort_by(|a, b| compare_devices(a, b, &config.pinned_devices));
+    Ok(devices)
+}
+
+/// Tab-separated `address state battery name`, for `cut` and `awk`.
+fn list_line(device: &BluetoothDevice) -> String {
+    let state = match (device.connected, device.paired) {
+        (true, _) => "connected",
+        (false, true) => "paired",
+        (false, false) => "available",
+    };
+    let battery = device.battery.map(|level| format!("{}%", level)).unwrap_or_else(|| "-".to_string());
+    let name = privacy::mask_name(&device.name, &device.address);
+    format!("{}\t{}\t{}\t{}", privacy::mask_address(&device.address), state, battery, name)
+}
+
+async fn print_status(service: &BluetoothService, config: &Config) -> Result<(), BluetoothError> {
+    let adapter = service.current_adapter().await?;
+    let rfkill = service.rfkill_state();
+    let power = if rfkill.hard {
+        "blocked (hardware)"
+    } else if rfkill.soft {
+        "blocked (software)"
+    } else if service.is_powered().await? {
+        "on"
+    } else {
+        "off"
+    };
+    let connected: Vec<String> = sorted_devices(service, config)
+        .await?
+        .into_iter()
+        .filter(|device| device.connected)
+        .map(|device| privacy::mask_name(&device.name, &device.address))
+        .collect();
+
+    println!("adapter: {}", adapter.as_deref().unwrap_or("none"));
+    println!("power: {}", power);
+    println!("connected: {}", connected.join(", "));
+    Ok(())
+}
+
+/// Snapshot for `status --json` and the first line of `watch --json`.
+async fn status(service: &BluetoothService, config: &Config) -> Result<json::Status, BluetoothError> {
+    let devices = sorted_devices(service, config).await?;
+    Ok(json::Status {
+        state: json::State::Ready,
+        reason: None,
+        adapter: service.current_adapter().await?,
+        adapters: service.adapters().await?.iter().map(json::Adapter::from).collect(),
+        powered: service.is_powered().await?,
+        rfkill: service.rfkill_state().into(),
+        devices: devices.iter().map(json::Device::from).collect(),
+    })
+}
+
+/// Anything `watch` and `bar` react to.
+enum Update {
+    State(ServiceState),
+    Rfkill(RfkillState),
+    Event(BluetoothEvent),
+}
+
+/// Follows the service state, rfkill and device events for the commands
+/// that keep running, subscribing to events again whenever Bluetooth comes
+/// back.
+struct Follower {
+    updates: BoxStream<'static, Update>,
+    events: BoxStream<'static, BluetoothEvent>,
+}
+
+impl Follower {
+    fn new(service: &BluetoothService) -> Self {
+        let rfkill = match service.watch_rfkill() {
+            Ok(changes) => changes.map(Update::Rfkill).boxed(),
+            Err(e) => {
+                eprintln!("Failed to watch rfkill: {:#}", e);
+                stream::pending().boxed()
+            }
+        };
+        Self {
+            updates: stream::select(service.watch_state().map(Update::State), rfkill).boxed(),
+            events: stream::pending().boxed(),
+        }
+    }
+
+    /// The next update; `None` once the service has stopped. Events are
+    /// subscribed before `Ready` is returned, so a snapshot taken then
+    /// misses nothing.
+    async fn next(&mut self, service: &BluetoothService) -> Option<Update> {
+        loop {
+            let update = match future::select(self.updates.next(), self.events.next()).await {
+                future::Either::Left((update, _)) => update?,
+                future::Either::Right((Some(event), _)) => Update::Event(event),
+                future::Either::Right((None, _)) => {
+                    self.events = stream::pending().boxed();
+                    continue;
+                }
+            };
+
+            match &update {
+                Update::State(ServiceState::Ready) => {
+                    self.events = match service.subscribe().await {
+                        Ok(events) => events,
+                        Err(e) => {
+                            let message = privacy::mask_text(&format!("{:#}", e));
+                            eprintln!("Failed to subscribe to Bluetooth events: {}", message);
+                            stream::pending().boxed()
+                        }
+                    };
+                }
+                Update::State(ServiceState::Unavailable(_)) => self.events = stream::pending().boxed(),
+                _ => {}
+            }
+            return Some(update);
+        }
+    }
+}
+
+/// Prints the status, then every change until interrupted. Unlike the
+/// other commands it keeps running while Bluetooth is unavailable and
+/// prints a fresh status whenever it comes back.
+async fn watch(service: &BluetoothService, config: &Config, json: bool) -> Result<(), Failure> {
+    let mut follower = Follower::new(service);
+    while let Some(update) = follower.next(service).await {
+        match update {
+            Update::State(ServiceState::Connecting) => {}
+            Update::State(ServiceState::Ready) if json => {
+                let status = status(service, config)
+                    .await
+                    .unwrap_or_else(|e| json::Status::unavailable(e.to_string(), service.rfkill_state()));
+                println!("{}", json::line(json::Event::Status(status)));
+            }
+            Update::State(ServiceState::Ready) => {
+                if let Err(e) = print_status(service, config).await {
+                    println!("unavailable\t{}", privacy::mask_text(e.message()))
+                }
+            }
+            Update::State(ServiceState::Unavailable(reason)) if json => {
+                let status = json::Status::unavailable(reason, service.rfkill_state());
+                println!("{}", json::line(json::Event::Status(status)));
+            }
+            Update::State(ServiceState::Unavailable(reason)) => {
+                println!("unavailable\t{}", privacy::mask_text(&reason))
+            }
+            Update::Rfkill(state) if json => {
+                println!("{}", json::line(json::Event::RfkillChanged { rfkill: state.into() }))
+            }
+            Update::Rfkill(state) => println!("rfkill\tsoft={}\thard={}", state.soft, state.hard),
+            Update::Event(event) if json => println!("{}", json::line(json::Event::from(&event))),
+            Update::Event(event) => println!("{}", event_line(&event)),
+        }
+    }
+    Err(Failure::unavailable("Bluetooth service stopped"))
+}
+
+/// Prints a bar line whenever what it shows changes. Waits out startup so
+/// the first line isn't a spurious "unavailable".
+async fn run_bar(service: &BluetoothService, config: &Config, options: &bar::Options) -> Result<(), Failure> {
+    let mut follower = Follower::new(service);
+    // Last settled state; `None` until the service has connected for the first time
+    let mut state = None;
+    let mut last_line = None;
+
+    while let Some(update) = follower.next(service).await {
+        match update {
+            Update::State(ServiceState::Connecting) => continue,
+            Update::State(settled) => state = Some(settled),
+            Update::Rfkill(_) | Update::Event(_) => {}
+        }
+
+        let snapshot = match &state {
+            None => continue,
+            Some(ServiceState::Unavailable(reason)) => bar::Snapshot::unavailable(reason.clone()),
+            Some(_) => bar_snapshot(service, config)
+                .await
+                .unwrap_or_else(|e| bar::Snapshot::unavailable(e.to_string())),
+        };
+        let line = bar::render(&snapshot, config, options);
+        if last_line.as_ref() != Some(&line) {
+            println!("{}", line);
+            last_line = Some(line);
+        }
+        if options.once {
+            return Ok(());
+        }
+    }
+    Err(Failure::unavailable("Bluetooth service stopped"))
+}
+
+async fn bar_snapshot(service: &BluetoothService, config: &Config) -> Result<bar::Snapshot, BluetoothError> {
+    let rfkill = service.rfkill_state();
+    let state = if rfkill.soft || rfkill.hard {
+        bar::State::Blocked
+    } else if service.is_powered().await? {
+        bar::State::On
+    } else {
+        bar::State::Off
+    };
+    Ok(bar::Snapshot {
+        state,
+        adapter: service.current_adapter().await?,
+        devices: sorted_devices(service, config).await?,
+    })
+}
+
+/// Tab-separated `kind details` line for `watch` without `--json`; device
+/// changes reuse the `list` columns.
+fn event_line(event: &BluetoothEvent) -> String {
+    match event {
+        BluetoothEvent::DeviceAdded(device) => format!("added\t{}", list_line(device)),
+        BluetoothEvent::DeviceChanged(device) => format!("changed\t{}", list_line(device)),
+        BluetoothEvent::DeviceRemoved(address) => format!("removed\t{}", privacy::mask_address(address)),
+        BluetoothEvent::PowerChanged(powered) => format!("power\t{}", if *powered { "on" } else { "off" }),
+        BluetoothEvent::AdapterAdded(adapter) => format!("adapter-added\t{}", adapter.name),
+        BluetoothEvent::AdapterRemoved(name) => format!("adapter-removed\t{}", name),
+    }
+}
+
+/// Resolves an address or a case-insensitive name to one known device.
+async fn find_device(service: &BluetoothService, query: &str) -> Result<BluetoothDevice, Failure> {
+    let devices = service.get_devices().await?;
+    if let Ok(address) = query.parse::<Address>() {
+        return devices
+            .into_iter()
+            .find(|device| device.address == address)
+            .ok_or_else(|| Failure::no_device(format!("no device with address {}", query)));
+    }
+
+    let mut matches: Vec<BluetoothDevice> = devices
+        .into_iter()
+        .filter(|device| {
+            device.name.eq_ignore_ascii_case(query)
+                || device.remote_name.as_deref().is_some_and(|name| name.eq_ignore_ascii_case(query))
+        })
+        .collect();
+    match matches.len() {
+        0 => Err(Failure::no_device(format!("no device named '{}'", query))),
+        1 => Ok(matches.remove(0)),
+        _ => {
+            let addresses: Vec<String> = matches.iter().map(|d| privacy::mask_address(&d.address)).collect();
+            Err(Failure::no_device(format!(
+                "'{}' matches several devices ({}); use an address",
+                query,
+                addresses.join(", ")
+            )))
+        }
+    }
+}
+
+/// Pairs `device`, answering agent prompts on the terminal.
+async fn pair(service: &BluetoothService, device: &BluetoothDevice) -> Result<(), BluetoothError> {
+    let mut requests = service.pairing_requests().await?;
+    let pairing = service.pair_device(device.address);
+    futures::pin_mut!(pairing);
+
+    loop {
+        match future::select(pairing, requests.next()).await {
+            future::Either::Left((result, _)) => return result,
+            future::Either::Right((Some(request), pending)) => {
+                answer(request);
+                pairing = pending;
+            }
+            future::Either::Right((None, pending)) => return pending.await,
+        }
+    }
+}
+
+fn answer(request: PairingRequest) {
+    match request {
+        PairingRequest::PinCode { reply, .. } => match prompt("PIN code: ") {
+            Some(pin) if !pin.is_empty() => reply.accept(pin),
+            _ => reply.reject(),
+        },
+        PairingRequest::Passkey { reply, .. } => match prompt("Passkey: ").and_then(|key| key.parse().ok()) {
+            Some(passkey) => reply.accept(passkey),
+            None => reply.reject(),
+        },
+        PairingRequest::DisplayPasskey { passkey, entered, .. } => {
+            if entered == 0 {
+                eprintln!("Type {:06} on the device, then press Enter on it", passkey);
+            }
+        }
+        PairingRequest::Confirmation { passkey, reply, .. } => {
+            match prompt(&format!("Does the device show {:06}? [y/N] ", passkey)).as_deref() {
+                Some("y" | "Y" | "yes") => reply.accept(()),
+                _ => reply.reject(),
+            }
+        }
+        PairingRequest::AuthorizeService { service, reply, .. } => {
+            match prompt(&format!("Allow the device to use {}? [y/N] ", service)).as_deref() {
+                Some("y" | "Y" | "yes") => reply.accept(()),
+                _ => reply.reject(),
+            }
+        }
+    }
+}
+
+/// Asks on stderr, so stdout stays parseable, and reads one trimmed line
+/// from stdin; `None` at end of input.
+fn prompt(question: &str) -> Option<String> {
+    eprint!("{}", question);
+    io::stderr().flush().ok()?;
+    let mut line = String::new();
+    match io::stdin().lock().read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line.trim().to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn json_flag_may_appear_anywhere() {
+        for line in ["--json list", "list --json", "connect --json Headphones"] {
+            let (json, rest) = take_json(&args(line));
+            assert!(json, "{}", line);
+            assert!(!rest.iter().any(|arg| arg == "--json"), "{}", line);
+        }
+        assert_eq!(take_json(&args("list")), (false, args("list")));
+        assert_eq!(
+            parse(&take_json(&args("connect --json Headphones")).1),
+            Ok(Command::Once(Action::Connect("Headphones".to_string())))
+        );
+    }
+
+    #[test]
+    fn parses_power() {
+        assert_eq!(parse(&args("power on")), Ok(Command::Once(Action::Power(Power::On))));
+        assert_eq!(parse(&args("power off")), Ok(Command::Once(Action::Power(Power::Off))));
+        assert_eq!(parse(&args("power toggle")), Ok(Command::Once(Action::Power(Power::Toggle))));
+        assert!(parse(&args("power")).is_err());
+        assert!(parse(&args("power up")).is_err());
+    }
+
+    #[test]
+    fn parses_bar_options() {
+        let expected = bar::Options {
+            output: bar::Output::Waybar,
+            once: true,
+            format: Some("{name}".to_string()),
+        };
+        assert_eq!(parse(&args("bar --waybar --once --format {name}")), Ok(Command::Bar(expected)));
+        assert!(parse(&args("bar --format")).is_err());
+        assert!(parse(&args("bar --polybar")).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_usage() {
+        assert_eq!(parse(&args("frobnicate")), Err("unknown command 'frobnicate'".to_string()));
+        assert!(parse(&args("connect")).is_err());
+        assert!(parse(&args("list everything")).is_err());
+    }
+
+    #[test]
+    fn usage_errors_exit_with_2() {
+        assert_eq!(run(&args("frobnicate")), ExitCode::from(status::USAGE));
+        assert_eq!(run(&args("frobnicate --json")), ExitCode::from(status::USAGE));
+        assert_eq!(run(&args("bar --json")), ExitCode::from(status::USAGE));
+    }
+}
@@ -4,16 +4,91 @@ use std::path::PathBuf;
 use directories::ProjectDirs;
 use anyhow::Result;
 
+use crate::bluetooth::{BluetoothDevice, RFKILL_PATH};
+use crate::privacy::AddressMask;
+
+/// Which Bluetooth stack the widget drives.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// The system bluetoothd.
+    Bluez,
+    /// Simulated devices loaded from `simulation_scenario` (or a built-in demo).
+    Simulation,
+}
+
+/// A device kept at the top of the list, in the order pins appear in the config.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PinnedDevice {
+    pub address: String,
+    /// Also match devices with this name, e.g. earbuds that come back with a
+    /// new random address after being reset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl PinnedDevice {
+    pub fn new(device: &BluetoothDevice) -> Self {
+        Self {
+            address: device.address.to_string(),
+            name: None,
+        }
+    }
+
+    pub fn matches(&self, device: &BluetoothDevice) -> bool {
+        self.address.eq_ignore_ascii_case(&device.address.to_string())
+            || self.name.as_deref() == Some(device.name.as_str())
+    }
+}
+
+/// What the widget does with adapter power when it starts.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum PowerPolicy {
+    /// Leave power as it is.
+    #[default]
+    Leave,
+    /// Turn the adapter on.
+    AlwaysOn,
+    /// Restore the state last set with the header switch.
+    RestoreLast,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Config {
     pub auto_hide_delay: u64,
     pub refresh_interval: u64,
+    /// Seconds a scan runs before stopping on its own; 0 scans until the window closes.
+    pub discovery_timeout: u64,
     pub show_battery_levels: bool,
     pub show_device_addresses: bool,
+    /// Applied wherever an address is shown or logged, including the details view.
+    pub address_mask: AddressMask,
     pub window_width: i32,
     pub window_height: i32,
     pub theme: String,
-    pub enable_bluetooth_functionality: bool,
+    pub pinned_devices: Vec<PinnedDevice>,
+    pub backend: BackendKind,
+    /// Adapter picked in the header, e.g. `hci1`; the default adapter when unset or absent.
+    pub adapter: Option<String>,
+    pub power_policy: PowerPolicy,
+    /// Last power state set with the header switch, for `PowerPolicy::RestoreLast`.
+    pub last_powered: Option<bool>,
+    /// rfkill control device; point it at a file of rfkill events to fake blocks.
+    pub rfkill_path: PathBuf,
+    pub simulation_scenario: Option<PathBuf>,
+    /// Text `bar` prints while a device is connected; see the README for placeholders.
+    pub bar_format: String,
+    /// Text `bar` prints while Bluetooth is on but nothing is connected.
+    pub bar_format_disconnected: String,
+    /// Text `bar` prints while Bluetooth is off, blocked or unavailable.
+    pub bar_format_off: String,
+    /// One tooltip line per connected device, for Waybar.
+    pub bar_tooltip_format: String,
+    /// Read from configs written before `backend` existed; `false` selects simulation.
+    #[serde(rename = "enable_bluetooth_functionality", skip_serializing)]
+    legacy_enable_bluetooth: Option<bool>,
 }
 
 impl Default for Config {
@@ -21,12 +96,25 @@ impl Default for Config {
         Self {
             auto_hide_delay: 100,
             refresh_interval: 5000,
+            discovery_timeout: 60,
             show_battery_levels: true,
             show_device_addresses: true,
+            address_mask: AddressMask::Full,
             window_width: 300,
             window_height: 400,
             theme: "auto".to_string(),
-            enable_bluetooth_functionality: true,
+            pinned_devices: Vec::new(),
+            backend: BackendKind::Bluez,
+            adapter: None,
+            power_policy: PowerPolicy::Leave,
+            last_powered: None,
+            rfkill_path: PathBuf::from(RFKILL_PATH),
+            simulation_scenario: None,
+            bar_format: "{name} {battery}".to_string(),
+            bar_format_disconnected: "On".to_string(),
+            bar_format_off: "Off".to_string(),
+            bar_tooltip_format: "{name} {battery}".to_string(),
+            legacy_enable_bluetooth: None,
         }
     }
 }
@@ -38,7 +126,11 @@ impl Config {
         if let Some(path) = &config_path {
             if path.exists() {
                 if let Ok(content) = fs::read_to_string(path) {
-                    if let Ok(config) = serde_json::from_str(&content) {
+                    if let Ok(mut config) = serde_json::from_str::<Config>(&content) {
+                        if config.legacy_enable_bluetooth.take() == Some(false) {
+                            config.backend = BackendKind::Simulation;
+                            config.save().ok();
+                        }
                         return config;
                     }
                 }
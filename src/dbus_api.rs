@@ -0,0 +1,244 @@
+use bluer::Address;
+use futures::future::{self, Either};
+use futures::stream::{self, BoxStream, StreamExt};
+use gtk4::prelude::*;
+use gtk4::{gio, Application};
+use anyhow::{anyhow, Result};
+
+use crate::bluetooth::{
+    compare_devices, BluetoothDevice, BluetoothError, BluetoothEvent, BluetoothService, ServiceState,
+};
+use crate::privacy;
+use crate::ui::instance::{self, WindowRequest};
+
+/// Well-known name owned next to the application id.
+const BUS_NAME: &str = "org.bluewidget";
+const OBJECT_PATH: &str = "/org/bluewidget/Manager";
+const INTERFACE: &str = "org.bluewidget.Manager";
+
+const INTROSPECTION: &str = r#"
+<node>
+  <interface name="org.bluewidget.Manager">
+    <method name="Show"/>
+    <method name="Hide"/>
+    <method name="Toggle"/>
+    <method name="Connect">
+      <arg name="address" type="s" direction="in"/>
+    </method>
+    <method name="Disconnect">
+      <arg name="address" type="s" direction="in"/>
+    </method>
+    <method name="SetPowered">
+      <arg name="powered" type="b" direction="in"/>
+    </method>
+    <method name="GetPowered">
+      <arg name="powered" type="b" direction="out"/>
+    </method>
+    <method name="GetDevices">
+      <arg name="devices" type="aa{sv}" direction="out"/>
+    </method>
+    <signal name="DeviceChanged">
+      <arg name="device" type="a{sv}"/>
+    </signal>
+    <signal name="DeviceRemoved">
+      <arg name="address" type="s"/>
+    </signal>
+    <signal name="PowerChanged">
+      <arg name="powered" type="b"/>
+    </signal>
+  </interface>
+</node>
+"#;
+
+/// Exports `org.bluewidget.Manager` on the application's session bus
+/// connection, so panels and scripts can drive the widget without starting
+/// a process per call. Call once the application is registered, i.e. from
+/// `startup`; method calls then arrive on the GTK main context.
+pub fn export(app: &Application) -> Result<()> {
+    let connection = app.dbus_connection().ok_or_else(|| anyhow!("application has no session bus connection"))?;
+    let node = gio::DBusNodeInfo::for_xml(INTROSPECTION)?;
+    let interface = node
+        .lookup_interface(INTERFACE)
+        .ok_or_else(|| anyhow!("{} missing from introspection", INTERFACE))?;
+
+    let app = app.clone();
+    let service = instance::service();
+    let calls = service.clone();
+    connection
+        .register_object(OBJECT_PATH, &interface)
+        .method_call(move |_, _, _, _, method, params, invocation| {
+            handle_call(&app, &calls, method, params, invocation)
+        })
+        .build()?;
+
+    gio::bus_own_name_on_connection(
+        &connection,
+        BUS_NAME,
+        gio::BusNameOwnerFlags::NONE,
+        |_, _| {},
+        |_, name| eprintln!("Could not own {} on the session bus", name),
+    );
+
+    glib::spawn_future_local(forward_events(connection, service));
+    Ok(())
+}
+
+fn handle_call(
+    app: &Application,
+    service: &BluetoothService,
+    method: &str,
+    params: glib::Variant,
+    invocation: gio::DBusMethodInvocation,
+) {
+    let window_request = match method {
+        "Show" => Some(WindowRequest::Show),
+        "Hide" => Some(WindowRequest::Hide),
+        "Toggle" => Some(WindowRequest::Toggle),
+        _ => None,
+    };
+    if let Some(request) = window_request {
+        instance::handle(app, request);
+        return invocation.return_value(None);
+    }
+
+    let service = service.clone();
+    match method {
+        "Connect" | "Disconnect" => {
+            let connect = method == "Connect";
+            let Some(address) = params.get::<(String,)>().and_then(|(address,)| address.parse::<Address>().ok())
+            else {
+                return invocation
+                    .return_dbus_error("org.freedesktop.DBus.Error.InvalidArgs", "Expected a Bluetooth address");
+            };
+            glib::spawn_future_local(async move {
+                let result = if connect {
+                    match service.connect_device(address).await {
+                        Err(BluetoothError::AlreadyConnected) => Ok(()),
+                        result => result,
+                    }
+                } else {
+                    service.disconnect_device(address).await
+                };
+                reply(invocation, result.map(|()| None));
+            });
+        }
+        "SetPowered" => {
+            let Some((powered,)) = params.get::<(bool,)>() else {
+                return invocation.return_dbus_error("org.freedesktop.DBus.Error.InvalidArgs", "Expected a boolean");
+            };
+            glib::spawn_future_local(async move {
+                reply(invocation, service.set_powered(powered).await.map(|()| None));
+            });
+        }
+        "GetPowered" => {
+            glib::spawn_future_local(async move {
+                let powered = service.is_powered().await;
+                reply(invocation, powered.map(|powered| Some((powered,).to_variant())));
+            });
+        }
+        "GetDevices" => {
+            glib::spawn_future_local(async move {
+                let devices = service.get_devices().await.map(|mut devices| {
+                    let config = instance::config();
+                    let pinned = config.borrow();
+                    devices.sort_by(|a, b| compare_devices(a, b, &pinned.pinned_devices));
+                    let devices = glib::Variant::array_from_iter_with_type(
+                        glib::VariantTy::VARDICT,
+                        devices.iter().map(device_dict),
+                    );
+                    Some(glib::Variant::tuple_from_iter([devices]))
+                });
+                reply(invocation, devices);
+            });
+        }
+        _ => invocation.return_dbus_error(
+            "org.freedesktop.DBus.Error.UnknownMethod",
+            &format!("{} has no method {}", INTERFACE, method),
+        ),
+    }
+}
+
+/// Finishes a call, turning a failure into `org.bluewidget.Error.<Kind>`,
+/// e.g. `org.bluewidget.Error.PageTimeout`.
+fn reply(invocation: gio::DBusMethodInvocation, result: Result<Option<glib::Variant>, BluetoothError>) {
+    match result {
+        Ok(value) => invocation.return_value(value.as_ref()),
+        Err(error) => {
+            // `page-timeout` becomes `PageTimeout`
+            let name: String = error
+                .kind()
+                .split('-')
+                .map(|word| {
+                    let mut chars = word.chars();
+                    let first = chars.next().map(|c| c.to_ascii_uppercase().to_string()).unwrap_or_default();
+                    first + chars.as_str()
+                })
+                .collect();
+            let message = match error.action() {
+                Some(action) => format!("{}. {}", error, action),
+                None => error.to_string(),
+            };
+            invocation.return_dbus_error(&format!("org.bluewidget.Error.{}", name), &message);
+        }
+    }
+}
+
+/// A device as `a{sv}`. Addresses are sent in full whatever
+/// `address_mask` says, since callers pass them back to `Connect`;
+/// names are masked, as they are everywhere they're shown.
+fn device_dict(device: &BluetoothDevice) -> glib::Variant {
+    let dict = glib::VariantDict::new(None);
+    dict.insert_value("address", &device.address.to_string().to_variant());
+    dict.insert_value("name", &privacy::mask_name(&device.name, &device.address).to_variant());
+    if let Some(remote_name) = &device.remote_name {
+        dict.insert_value("remote_name", &remote_name.to_variant());
+    }
+    dict.insert_value("icon", &device.get_icon_name().to_variant());
+    dict.insert_value("connected", &device.connected.to_variant());
+    dict.insert_value("paired", &device.paired.to_variant());
+    dict.insert_value("trusted", &device.trusted.to_variant());
+    dict.insert_value("blocked", &device.blocked.to_variant());
+    if let Some(battery) = device.battery {
+        dict.insert_value("battery", &battery.to_variant());
+    }
+    dict.end()
+}
+
+/// Re-emits backend events as signals for as long as the application runs,
+/// subscribing again whenever Bluetooth becomes ready.
+async fn forward_events(connection: gio::DBusConnection, service: BluetoothService) {
+    let mut states = service.watch_state();
+    let mut events: BoxStream<'static, BluetoothEvent> = stream::pending().boxed();
+    loop {
+        match future::select(states.next(), events.next()).await {
+            Either::Left((Some(ServiceState::Ready), _)) => {
+                events = match service.subscribe().await {
+                    Ok(events) => events,
+                    Err(e) => {
+                        eprintln!("Failed to subscribe to Bluetooth events: {}", e);
+                        stream::pending().boxed()
+                    }
+                };
+            }
+            Either::Left((Some(_), _)) => events = stream::pending().boxed(),
+            Either::Left((None, _)) => return,
+            Either::Right((Some(event), _)) => emit(&connection, &event),
+            Either::Right((None, _)) => events = stream::pending().boxed(),
+        }
+    }
+}
+
+fn emit(connection: &gio::DBusConnection, event: &BluetoothEvent) {
+    let (signal, value) = match event {
+        BluetoothEvent::DeviceAdded(device) | BluetoothEvent::DeviceChanged(device) => {
+            ("DeviceChanged", device_dict(device))
+        }
+        BluetoothEvent::DeviceRemoved(address) => ("DeviceRemoved", address.to_string().to_variant()),
+        BluetoothEvent::PowerChanged(powered) => ("PowerChanged", powered.to_variant()),
+        BluetoothEvent::AdapterAdded(_) | BluetoothEvent::AdapterRemoved(_) => return,
+    };
+    let params = glib::Variant::tuple_from_iter([value]);
+    if let Err(e) = connection.emit_signal(None, OBJECT_PATH, INTERFACE, signal, Some(&params)) {
+        eprintln!("Failed to emit {}: {}", signal, e);
+    }
+}
@@ -0,0 +1,178 @@
+use serde::Serialize;
+
+use crate::bluetooth::{AdapterInfo, BluetoothDevice, BluetoothEvent, RfkillState};
+use crate::privacy;
+
+/// Schema version of `--json` output, present in every document and event.
+/// Fields are only ever added; anything that would break readers bumps it.
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Serialize)]
+pub struct Device {
+    /// Masked according to `address_mask`.
+    pub address: String,
+    pub name: String,
+    pub remote_name: Option<String>,
+    pub icon: String,
+    pub connected: bool,
+    pub paired: bool,
+    pub trusted: bool,
+    pub blocked: bool,
+    /// Charge in percent, or `null` if the device doesn't report one.
+    pub battery: Option<u8>,
+}
+
+impl From<&BluetoothDevice> for Device {
+    fn from(device: &BluetoothDevice) -> Self {
+        Self {
+            address: privacy::mask_address(&device.address),
+            name: privacy::mask_name(&device.name, &device.address),
+            remote_name: device.remote_name.clone(),
+            icon: device.get_icon_name(),
+            connected: device.connected,
+            paired: device.paired,
+            trusted: device.trusted,
+            blocked: device.blocked,
+            battery: device.battery,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Adapter {
+    pub name: String,
+    pub alias: String,
+    pub address: String,
+}
+
+impl From<&AdapterInfo> for Adapter {
+    fn from(adapter: &AdapterInfo) -> Self {
+        Self {
+            name: adapter.name.clone(),
+            alias: adapter.alias.clone(),
+            address: privacy::mask_address(&adapter.address),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Rfkill {
+    pub soft: bool,
+    pub hard: bool,
+}
+
+impl From<RfkillState> for Rfkill {
+    fn from(state: RfkillState) -> Self {
+        Self {
+            soft: state.soft,
+            hard: state.hard,
+        }
+    }
+}
+
+/// Whether the widget can reach Bluetooth at all.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum State {
+    Ready,
+    Unavailable,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Status {
+    pub state: State,
+    /// Why Bluetooth is unavailable; `null` while ready.
+    pub reason: Option<String>,
+    /// Selected adapter name, e.g. `hci0`.
+    pub adapter: Option<String>,
+    pub adapters: Vec<Adapter>,
+    pub powered: bool,
+    pub rfkill: Rfkill,
+    /// Every known device, in the order the widget lists them.
+    pub devices: Vec<Device>,
+}
+
+impl Status {
+    pub fn unavailable(reason: String, rfkill: RfkillState) -> Self {
+        Self {
+            state: State::Unavailable,
+            reason: Some(privacy::mask_text(&reason)),
+            adapter: None,
+            adapters: Vec::new(),
+            powered: false,
+            rfkill: rfkill.into(),
+            devices: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Error {
+    /// Stable identifier, e.g. `page-timeout` or `no-device`; see
+    /// `BluetoothError::kind`.
+    pub kind: String,
+    pub message: String,
+    /// What the user can try next.
+    pub action: Option<String>,
+}
+
+/// One line of `watch --json`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    /// A full snapshot: first, and again whenever Bluetooth comes back.
+    Status(Status),
+    DeviceAdded { device: Device },
+    DeviceChanged { device: Device },
+    DeviceRemoved { address: String },
+    PowerChanged { powered: bool },
+    AdapterAdded { adapter: Adapter },
+    AdapterRemoved { name: String },
+    RfkillChanged { rfkill: Rfkill },
+}
+
+impl From<&BluetoothEvent> for Event {
+    fn from(event: &BluetoothEvent) -> Self {
+        match event {
+            BluetoothEvent::DeviceAdded(device) => Self::DeviceAdded { device: device.into() },
+            BluetoothEvent::DeviceChanged(device) => Self::DeviceChanged { device: device.into() },
+            BluetoothEvent::DeviceRemoved(address) => Self::DeviceRemoved {
+                address: privacy::mask_address(address),
+            },
+            BluetoothEvent::PowerChanged(powered) => Self::PowerChanged { powered: *powered },
+            BluetoothEvent::AdapterAdded(adapter) => Self::AdapterAdded { adapter: adapter.into() },
+            BluetoothEvent::AdapterRemoved(name) => Self::AdapterRemoved { name: name.clone() },
+        }
+    }
+}
+
+/// Wraps a document with the schema version, e.g. `{"version":1,"devices":[...]}`.
+#[derive(Debug, Serialize)]
+pub struct Versioned<T: Serialize> {
+    pub version: u32,
+    #[serde(flatten)]
+    pub body: T,
+}
+
+/// Serializes `body` as one line of JSON with the schema version.
+pub fn line<T: Serialize>(body: T) -> String {
+    serde_json::to_string(&Versioned { version: VERSION, body }).expect("JSON output types always serialize")
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeviceList {
+    pub devices: Vec<Device>,
+}
+
+/// Result of an action such as `connect`; `error` is set when it failed.
+#[derive(Debug, Serialize)]
+pub struct Outcome {
+    pub ok: bool,
+    pub error: Option<Error>,
+    /// The device acted on, as it was before the action.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<Device>,
+    /// Adapter power after a `power` command.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub powered: Option<bool>,
+}
@@ -1,20 +1,60 @@
+mod bar;
 mod bluetooth;
+mod cli;
 mod config;
+mod dbus_api;
+mod json;
+mod privacy;
 mod ui;
 
 use gtk4::prelude::*;
-use gtk4::Application;
-use ui::window::Window;
+use gtk4::{gio, Application};
+use std::process::{ExitCode, Termination};
+use ui::instance::{self, WindowRequest};
+
+fn main() -> ExitCode {
+    // Arguments other than the window requests select a command-line
+    // subcommand, which runs without a display.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if WindowRequest::parse(&args).is_none() {
+        return cli::run(&args);
+    }
 
-fn main() {
     let app = Application::builder()
         .application_id("com.bluewidget")
+        .flags(gio::ApplicationFlags::HANDLES_COMMAND_LINE)
         .build();
 
-    app.connect_activate(|app| {
-        let window = Window::new(app);
-        window.window.present();
+    // Runs in the first instance for every launch, with that launch's
+    // arguments; later launches exit once it returns.
+    app.connect_command_line(|app, command_line| {
+        let args: Vec<String> = command_line
+            .arguments()
+            .iter()
+            .skip(1)
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        match WindowRequest::parse(&args) {
+            Some(request) => {
+                instance::handle(app, request);
+                0
+            }
+            None => {
+                command_line.printerr_literal(&format!("bluetooth-widget: unexpected arguments {:?}\n", args));
+                2
+            }
+        }
+    });
+
+    app.connect_startup(|app| {
+        if let Err(e) = dbus_api::export(app) {
+            eprintln!("Failed to export the D-Bus API: {:#}", e);
+        }
     });
 
-    app.run();
-}
\ No newline at end of file
+    // `Activate` on org.freedesktop.Application, e.g. from `gapplication launch`,
+    // which only reaches an instance that is already running
+    app.connect_activate(|app| instance::handle(app, WindowRequest::Toggle));
+
+    app.run().report()
+}
@@ -0,0 +1,181 @@
+use bluer::Address;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// How device addresses are shown in the window and written to logs, so
+/// screenshots and bug reports don't leak MAC addresses.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum AddressMask {
+    /// The whole address.
+    #[default]
+    Full,
+    /// Only the last two octets, e.g. `XX:XX:XX:XX:71:01`.
+    LastOctets,
+    /// A short hash that stays the same for a device, e.g. `dev-3fa2c1`.
+    /// Meant for telling devices apart in screenshots; it is not anonymous.
+    Hash,
+}
+
+impl AddressMask {
+    pub fn apply(self, address: &Address) -> String {
+        self.apply_with(address, ':')
+    }
+
+    /// Like `apply`, but with `separator` between the octets that are kept.
+    fn apply_with(self, address: &Address, separator: char) -> String {
+        match self {
+            Self::Full => address.to_string().replace(':', &separator.to_string()),
+            Self::LastOctets => {
+                format!("XX{0}XX{0}XX{0}XX{0}{1:02X}{0}{2:02X}", separator, address.0[4], address.0[5])
+            }
+            Self::Hash => format!("dev-{:06x}", fnv1a(&address.0) & 0xff_ffff),
+        }
+    }
+
+    /// `name` as it may be shown for the device at `address`. BlueZ names a
+    /// device that never sent one after its address, e.g. `00-1A-7D-DA-71-01`,
+    /// so addresses in the name are masked too, and an empty name shows as the
+    /// masked address.
+    pub fn apply_name(self, name: &str, address: &Address) -> String {
+        match name.is_empty() {
+            true => self.apply(address),
+            false => self.apply_text(name),
+        }
+    }
+
+    /// Masks every address in `text`, e.g. an error message, keeping the
+    /// separator it was written with: `:`, `-`, or `_` as in the
+    /// `dev_00_1A_7D_DA_71_01` form used in BlueZ object paths.
+    pub fn apply_text(self, text: &str) -> String {
+        if self == Self::Full {
+            return text.to_string();
+        }
+
+        let mut masked = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            match address_at(rest) {
+                Some((address, separator)) => {
+                    masked.push_str(&self.apply_with(&address, separator));
+                    rest = &rest[17..];
+                }
+                None => {
+                    masked.push(c);
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+        masked
+    }
+}
+
+static MASK: OnceLock<AddressMask> = OnceLock::new();
+
+/// Sets the mask for the rest of the process; later calls are ignored.
+pub fn set_mask(mask: AddressMask) {
+    let _ = MASK.set(mask);
+}
+
+fn mask() -> AddressMask {
+    MASK.get().copied().unwrap_or_default()
+}
+
+/// `address` as it may be shown or logged.
+pub fn mask_address(address: &Address) -> String {
+    mask().apply(address)
+}
+
+/// Masks every address in `text` with the process's mask; see `AddressMask::apply_text`.
+pub fn mask_text(text: &str) -> String {
+    mask().apply_text(text)
+}
+
+/// A device's name as it may be shown; see `AddressMask::apply_name`.
+pub fn mask_name(name: &str, address: &Address) -> String {
+    mask().apply_name(name, address)
+}
+
+/// Parses an address at the start of `text`, separated by `:`, `-` or `_`,
+/// and returns it with the separator.
+fn address_at(text: &str) -> Option<(Address, char)> {
+    let candidate = text.get(..17)?;
+    let bytes = candidate.as_bytes();
+    let separator = bytes[2];
+    let well_formed = matches!(separator, b':' | b'-' | b'_')
+        && bytes
+            .iter()
+            .enumerate()
+            .all(|(i, &b)| if i % 3 == 2 { b == separator } else { b.is_ascii_hexdigit() });
+    if !well_formed {
+        return None;
+    }
+    let address = candidate.replace(char::from(separator), ":").parse().ok()?;
+    Some((address, char::from(separator)))
+}
+
+/// 32-bit FNV-1a; fixed, unlike `DefaultHasher`, so a device keeps its hash
+/// across runs and Rust versions.
+fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, &byte| (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADPHONES: &str = "00:1A:7D:DA:71:01";
+
+    fn address(text: &str) -> Address {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn masks_addresses() {
+        assert_eq!(AddressMask::Full.apply(&address(HEADPHONES)), HEADPHONES);
+        assert_eq!(AddressMask::LastOctets.apply(&address(HEADPHONES)), "XX:XX:XX:XX:71:01");
+        assert_eq!(AddressMask::Hash.apply(&address(HEADPHONES)), "dev-549c9a");
+    }
+
+    #[test]
+    fn hashes_are_stable_and_distinct() {
+        let hash = AddressMask::Hash.apply(&address(HEADPHONES));
+        assert_eq!(hash, AddressMask::Hash.apply(&address(HEADPHONES)));
+        assert_eq!(AddressMask::Hash.apply(&address("00:1A:7D:DA:71:02")), "dev-549b07");
+    }
+
+    #[test]
+    fn masks_addresses_in_text() {
+        let text = "Failed to connect 00:1A:7D:DA:71:01: /org/bluez/hci0/dev_00_1A_7D_DA_71_02 not found";
+        assert_eq!(
+            AddressMask::LastOctets.apply_text(text),
+            "Failed to connect XX:XX:XX:XX:71:01: /org/bluez/hci0/dev_XX_XX_XX_XX_71_02 not found"
+        );
+        assert_eq!(
+            AddressMask::Hash.apply_text(text),
+            "Failed to connect dev-549c9a: /org/bluez/hci0/dev_dev-549b07 not found"
+        );
+        assert_eq!(AddressMask::Full.apply_text(text), text);
+    }
+
+    #[test]
+    fn masks_dashed_addresses() {
+        assert_eq!(AddressMask::LastOctets.apply_text("00-1A-7D-DA-71-01"), "XX-XX-XX-XX-71-01");
+        assert_eq!(AddressMask::Hash.apply_text("00-1A-7D-DA-71-01"), "dev-549c9a");
+    }
+
+    #[test]
+    fn masks_names_that_are_addresses() {
+        let headphones = address(HEADPHONES);
+        assert_eq!(AddressMask::LastOctets.apply_name("", &headphones), "XX:XX:XX:XX:71:01");
+        assert_eq!(AddressMask::LastOctets.apply_name("00-1A-7D-DA-71-01", &headphones), "XX-XX-XX-XX-71-01");
+        assert_eq!(AddressMask::LastOctets.apply_name("Headphones", &headphones), "Headphones");
+        assert_eq!(AddressMask::Full.apply_name("", &headphones), HEADPHONES);
+    }
+
+    #[test]
+    fn leaves_text_without_addresses_alone() {
+        let text = "Page Timeout after 5 s: 00:1A:7D:DA:71 is too short, 00-1A:7D-DA:71-01 mixes separators, café";
+        assert_eq!(AddressMask::LastOctets.apply_text(text), text);
+    }
+}
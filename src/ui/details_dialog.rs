@@ -0,0 +1,135 @@
+use gtk4::prelude::*;
+use gtk4::{Align, Box, Button, Grid, Label, Orientation, ScrolledWindow, Window};
+
+use crate::bluetooth::{appearance_category, major_class, service_name, short_uuid, DeviceDetails};
+use crate::privacy;
+
+/// Opens a window listing everything bluetoothd reports about a device.
+/// Values are selectable so they can be pasted into bug reports.
+pub fn show(parent: &impl IsA<Window>, details: &DeviceDetails) {
+    let window = Window::builder()
+        .title(format!("{} – Details", details.name))
+        .transient_for(parent)
+        .modal(true)
+        .destroy_with_parent(true)
+        .default_width(380)
+        .default_height(480)
+        .build();
+
+    let content = Box::builder()
+        .orientation(Orientation::Vertical)
+        .spacing(12)
+        .margin_start(16)
+        .margin_end(16)
+        .margin_top(16)
+        .margin_bottom(16)
+        .build();
+
+    let grid = Grid::builder()
+        .row_spacing(4)
+        .column_spacing(12)
+        .build();
+    let mut row = 0;
+    let mut add = |key: &str, value: String| {
+        let key = Label::builder()
+            .label(key)
+            .xalign(1.0)
+            .valign(Align::Start)
+            .css_classes(vec!["dim-label"])
+            .build();
+        let value = Label::builder()
+            .label(value)
+            .xalign(0.0)
+            .wrap(true)
+            .selectable(true)
+            .hexpand(true)
+            .build();
+        grid.attach(&key, 0, row, 1, 1);
+        grid.attach(&value, 1, row, 1, 1);
+        row += 1;
+    };
+
+    let flag = |value: bool| if value { "Yes" } else { "No" }.to_string();
+    let unknown = || "Unknown".to_string();
+
+    add("Name", details.name.clone());
+    add("Remote name", details.remote_name.clone().unwrap_or_else(unknown));
+    add("Address", format!("{} ({})", privacy::mask_address(&details.address), details.address_type));
+    add("Adapter", details.adapter.clone());
+    add("Icon", details.icon.clone().unwrap_or_else(unknown));
+    add(
+        "Class",
+        details
+            .class
+            .map(|class| format!("0x{:06X} ({})", class, major_class(class)))
+            .unwrap_or_else(unknown),
+    );
+    add(
+        "Appearance",
+        details
+            .appearance
+            .map(|appearance| format!("0x{:04X} ({})", appearance, appearance_category(appearance)))
+            .unwrap_or_else(unknown),
+    );
+    add("Modalias", details.modalias.clone().unwrap_or_else(unknown));
+    add(
+        "RSSI",
+        details.rssi.map(|rssi| format!("{} dBm", rssi)).unwrap_or_else(|| "Out of range".to_string()),
+    );
+    add("TX power", details.tx_power.map(|power| format!("{} dBm", power)).unwrap_or_else(unknown));
+    add("Battery", details.battery.map(|level| format!("{}%", level)).unwrap_or_else(unknown));
+    add("Connected", flag(details.connected));
+    add("Paired", flag(details.paired));
+    add("Bonded", details.bonded.map(flag).unwrap_or_else(unknown));
+    add("Trusted", flag(details.trusted));
+    add("Blocked", flag(details.blocked));
+    add("Legacy pairing", flag(details.legacy_pairing));
+    add("Services resolved", flag(details.services_resolved));
+    content.append(&grid);
+
+    let services_title = Label::builder()
+        .use_markup(true)
+        .label("<b>Services</b>")
+        .xalign(0.0)
+        .build();
+    content.append(&services_title);
+
+    let services = Box::builder()
+        .orientation(Orientation::Vertical)
+        .spacing(2)
+        .build();
+    if details.uuids.is_empty() {
+        services.append(&Label::builder().label("None reported").xalign(0.0).css_classes(vec!["dim-label"]).build());
+    }
+    for uuid in &details.uuids {
+        let text = match service_name(uuid) {
+            Some(name) => format!("{} ({})", name, short_uuid(uuid)),
+            None => short_uuid(uuid),
+        };
+        services.append(&Label::builder().label(text).xalign(0.0).selectable(true).wrap(true).build());
+    }
+    content.append(&services);
+
+    let scrolled = ScrolledWindow::builder()
+        .hscrollbar_policy(gtk4::PolicyType::Never)
+        .vexpand(true)
+        .child(&content)
+        .build();
+
+    let outer = Box::new(Orientation::Vertical, 0);
+    outer.append(&scrolled);
+
+    let close_button = Button::builder()
+        .label("Close")
+        .halign(Align::End)
+        .margin_end(16)
+        .margin_top(8)
+        .margin_bottom(16)
+        .build();
+    let w = window.clone();
+    close_button.connect_clicked(move |_| w.close());
+    outer.append(&close_button);
+
+    window.set_child(Some(&outer));
+    window.present();
+}
@@ -0,0 +1,89 @@
+use glib::prelude::*;
+use glib::subclass::prelude::*;
+
+use crate::bluetooth::BluetoothDevice;
+
+mod imp {
+    use super::*;
+    use glib::subclass::Signal;
+    use std::cell::{Cell, RefCell};
+    use std::sync::OnceLock;
+
+    #[derive(Default)]
+    pub struct DeviceObject {
+        pub device: RefCell<Option<BluetoothDevice>>,
+        pub pinned: Cell<bool>,
+        pub busy: Cell<bool>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for DeviceObject {
+        const NAME: &'static str = "BluewidgetDeviceObject";
+        type Type = super::DeviceObject;
+    }
+
+    impl ObjectImpl for DeviceObject {
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+            SIGNALS.get_or_init(|| vec![Signal::builder("changed").build()])
+        }
+    }
+}
+
+glib::wrapper! {
+    /// List model item holding one device; rows listen to `changed` instead of
+    /// being rebuilt when the device updates.
+    pub struct DeviceObject(ObjectSubclass<imp::DeviceObject>);
+}
+
+impl DeviceObject {
+    pub fn new(device: BluetoothDevice) -> Self {
+        let obj: Self = glib::Object::new();
+        obj.imp().device.replace(Some(device));
+        obj
+    }
+
+    pub fn device(&self) -> BluetoothDevice {
+        self.imp()
+            .device
+            .borrow()
+            .clone()
+            .expect("DeviceObject is always created with a device")
+    }
+
+    /// Replaces the device and emits `changed`.
+    pub fn update(&self, device: BluetoothDevice) {
+        self.imp().device.replace(Some(device));
+        self.emit_by_name::<()>("changed", &[]);
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        self.imp().pinned.get()
+    }
+
+    /// Sets whether the device matches a pin, emitting `changed` if that flips.
+    pub fn set_pinned(&self, pinned: bool) {
+        if self.imp().pinned.replace(pinned) != pinned {
+            self.emit_by_name::<()>("changed", &[]);
+        }
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.imp().busy.get()
+    }
+
+    /// Marks a connect, disconnect or pair as in flight, emitting `changed` if that flips.
+    pub fn set_busy(&self, busy: bool) {
+        if self.imp().busy.replace(busy) != busy {
+            self.emit_by_name::<()>("changed", &[]);
+        }
+    }
+
+    pub fn connect_changed<F: Fn(&Self) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+        self.connect_local("changed", false, move |values| {
+            let obj = values[0].get::<Self>().expect("changed is emitted by DeviceObject");
+            f(&obj);
+            None
+        })
+    }
+}
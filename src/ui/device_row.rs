@@ -1,18 +1,47 @@
 use gtk4::prelude::*;
-use gtk4::{Align, Box, Button, Image, Label, ListBoxRow, Orientation, Switch};
+use gtk4::{
+    gdk, gio, Align, Box, Button, GestureClick, GestureLongPress, Image, Label, Orientation, PopoverMenu,
+    Spinner, Switch, ToggleButton,
+};
+use bluer::Address;
+use std::cell::RefCell;
+use std::rc::Rc;
+
 use crate::bluetooth::BluetoothDevice;
+use crate::privacy;
+use crate::ui::device_object::DeviceObject;
 
+/// Row widgets for one list item. Rows are recycled by the `ListView`, so a
+/// row is bound to whichever `DeviceObject` it currently shows and follows
+/// that object's `changed` signal until it is rebound.
+#[derive(Clone)]
 pub struct DeviceRow {
-    pub row: ListBoxRow,
-    pub connect_switch: Option<Switch>,
-    pub pair_button: Option<Button>,
+    pub root: Box,
+    icon: Image,
+    name_label: Label,
+    addr_label: Label,
+    battery_icon: Image,
+    battery_label: Label,
+    show_battery: bool,
+    show_address: bool,
+    pub pin_button: ToggleButton,
+    spinner: Spinner,
+    pub connect_switch: Switch,
+    pub pair_button: Button,
+    /// Context menu actions; the toggles' state follows the bound device.
+    trusted_action: gio::SimpleAction,
+    blocked_action: gio::SimpleAction,
+    rename_action: gio::SimpleAction,
+    details_action: gio::SimpleAction,
+    forget_action: gio::SimpleAction,
+    bound: Rc<RefCell<Option<(DeviceObject, glib::SignalHandlerId)>>>,
 }
 
 impl DeviceRow {
-    pub fn new(device: &BluetoothDevice) -> Self {
-        let row = ListBoxRow::new();
-        
-        let box_container = Box::builder()
+    /// `show_battery` and `show_address` mirror `Config::show_battery_levels`
+    /// and `Config::show_device_addresses`.
+    pub fn new(show_battery: bool, show_address: bool) -> Self {
+        let root = Box::builder()
             .orientation(Orientation::Horizontal)
             .spacing(8)
             .margin_start(8)
@@ -23,11 +52,10 @@ impl DeviceRow {
 
         // Device icon
         let icon = Image::builder()
-            .icon_name(&device.get_icon_name())
             .pixel_size(16)
             .valign(Align::Center)
             .build();
-        box_container.append(&icon);
+        root.append(&icon);
 
         // Device info
         let info_box = Box::builder()
@@ -36,56 +64,295 @@ impl DeviceRow {
             .build();
 
         let name_label = Label::builder()
-            .label(&device.name)
             .use_markup(true)
             .xalign(0.0)
             .valign(Align::Center)
             .build();
-        name_label.set_markup(&format!("<b>{}</b>", glib::markup_escape_text(&device.name)));
         info_box.append(&name_label);
 
         let addr_label = Label::builder()
-            .label(&device.address.to_string())
+            .visible(show_address)
             .xalign(0.0)
             .valign(Align::Center)
             .css_classes(vec!["dim-label"])
             .build();
         info_box.append(&addr_label);
 
-        box_container.append(&info_box);
+        root.append(&info_box);
 
         // Spacer
         let spacer = Box::new(Orientation::Horizontal, 0);
         spacer.set_hexpand(true);
-        box_container.append(&spacer);
-
-        let mut connect_switch = None;
-        let mut pair_button = None;
-
-        if device.paired {
-            let switch = Switch::builder()
-                .active(device.connected)
-                .tooltip_text("Connect/Disconnect")
-                .valign(Align::Center)
-                .build();
-            box_container.append(&switch);
-            connect_switch = Some(switch);
-        } else {
-            let button = Button::builder()
-                .label("Pair")
-                .css_classes(vec!["flat"])
-                .valign(Align::Center)
-                .build();
-            box_container.append(&button);
-            pair_button = Some(button);
-        }
+        root.append(&spacer);
+
+        // Battery level, hidden for devices that don't report one
+        let battery_icon = Image::builder()
+            .pixel_size(16)
+            .valign(Align::Center)
+            .visible(false)
+            .build();
+        root.append(&battery_icon);
+
+        let battery_label = Label::builder()
+            .valign(Align::Center)
+            .css_classes(vec!["dim-label"])
+            .visible(false)
+            .build();
+        root.append(&battery_label);
+
+        let pin_button = ToggleButton::builder()
+            .icon_name("view-pin-symbolic")
+            .tooltip_text("Pin to top")
+            .css_classes(vec!["flat"])
+            .valign(Align::Center)
+            .build();
+        root.append(&pin_button);
+
+        // Shown while a connect, disconnect or pair is in flight
+        let spinner = Spinner::builder()
+            .visible(false)
+            .valign(Align::Center)
+            .build();
+        root.append(&spinner);
 
-        row.set_child(Some(&box_container));
+        let connect_switch = Switch::builder()
+            .tooltip_text("Connect/Disconnect")
+            .valign(Align::Center)
+            .build();
+        root.append(&connect_switch);
+
+        let pair_button = Button::builder()
+            .label("Pair")
+            .css_classes(vec!["flat"])
+            .valign(Align::Center)
+            .build();
+        root.append(&pair_button);
+
+        // Context menu on right click or long press
+        let trusted_action = gio::SimpleAction::new_stateful("trusted", None, &false.to_variant());
+        let blocked_action = gio::SimpleAction::new_stateful("blocked", None, &false.to_variant());
+        let rename_action = gio::SimpleAction::new("rename", None);
+        let details_action = gio::SimpleAction::new("details", None);
+        let forget_action = gio::SimpleAction::new("forget", None);
+        let actions = gio::SimpleActionGroup::new();
+        actions.add_action(&trusted_action);
+        actions.add_action(&blocked_action);
+        actions.add_action(&rename_action);
+        actions.add_action(&details_action);
+        actions.add_action(&forget_action);
+        root.insert_action_group("row", Some(&actions));
+
+        let menu = gio::Menu::new();
+        menu.append(Some("Rename…"), Some("row.rename"));
+        menu.append(Some("Details"), Some("row.details"));
+        let toggles = gio::Menu::new();
+        toggles.append(Some("Trusted"), Some("row.trusted"));
+        toggles.append(Some("Blocked"), Some("row.blocked"));
+        menu.append_section(None, &toggles);
+        let forget = gio::Menu::new();
+        forget.append(Some("Forget…"), Some("row.forget"));
+        menu.append_section(None, &forget);
+
+        let popover = PopoverMenu::from_model(Some(&menu));
+        popover.set_has_arrow(false);
+        popover.set_halign(Align::Start);
+        popover.set_parent(&root);
+        let p = popover.clone();
+        root.connect_destroy(move |_| p.unparent());
+
+        let show_menu = move |x: f64, y: f64| {
+            popover.set_pointing_to(Some(&gdk::Rectangle::new(x as i32, y as i32, 1, 1)));
+            popover.popup();
+        };
+
+        let right_click = GestureClick::builder().button(gdk::BUTTON_SECONDARY).build();
+        let show = show_menu.clone();
+        right_click.connect_pressed(move |_, _, x, y| show(x, y));
+        root.add_controller(right_click);
+
+        let long_press = GestureLongPress::builder().touch_only(true).build();
+        long_press.connect_pressed(move |_, x, y| show_menu(x, y));
+        root.add_controller(long_press);
 
         Self {
-            row,
+            root,
+            icon,
+            name_label,
+            addr_label,
+            battery_icon,
+            battery_label,
+            show_battery,
+            show_address,
+            pin_button,
+            spinner,
             connect_switch,
             pair_button,
+            trusted_action,
+            blocked_action,
+            rename_action,
+            details_action,
+            forget_action,
+            bound: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /// Shows `object` in this row, or clears the row when `None`.
+    pub fn bind(&self, object: Option<DeviceObject>) {
+        if let Some((old, handler)) = self.bound.borrow_mut().take() {
+            old.disconnect(handler);
+        }
+
+        if let Some(object) = object {
+            self.update(&object);
+            let row = self.clone();
+            let handler = object.connect_changed(move |obj| row.update(obj));
+            self.bound.replace(Some((object, handler)));
         }
     }
-}
\ No newline at end of file
+
+    /// Calls `f` when the user flips the switch; programmatic syncs to the
+    /// device's current state are ignored. The switch's state only follows
+    /// once `update` sees the operation finish.
+    pub fn connect_toggled<F: Fn(Address, bool) + 'static>(&self, f: F) {
+        let bound = self.bound.clone();
+        self.connect_switch.connect_state_set(move |_, state| {
+            let device = bound.borrow().as_ref().map(|(obj, _)| obj.device());
+            match device {
+                Some(device) if device.connected != state => {
+                    f(device.address, state);
+                    glib::Propagation::Stop
+                }
+                _ => glib::Propagation::Proceed,
+            }
+        });
+    }
+
+    /// Calls `f` with the new pin state when the user presses the pin button.
+    pub fn connect_pin_toggled<F: Fn(Address, bool) + 'static>(&self, f: F) {
+        let bound = self.bound.clone();
+        self.pin_button.connect_toggled(move |button| {
+            let object = bound.borrow().as_ref().map(|(obj, _)| obj.clone());
+            if let Some(object) = object {
+                if object.is_pinned() != button.is_active() {
+                    f(object.device().address, button.is_active());
+                }
+            }
+        });
+    }
+
+    pub fn connect_pair_clicked<F: Fn(Address) + 'static>(&self, f: F) {
+        let bound = self.bound.clone();
+        self.pair_button.connect_clicked(move |_| {
+            let address = bound.borrow().as_ref().map(|(obj, _)| obj.device().address);
+            if let Some(address) = address {
+                f(address);
+            }
+        });
+    }
+
+    /// Calls `f` with the requested trust when the user picks Trusted.
+    pub fn connect_trusted_toggled<F: Fn(Address, bool) + 'static>(&self, f: F) {
+        let bound = self.bound.clone();
+        self.trusted_action.connect_activate(move |_, _| {
+            let device = bound.borrow().as_ref().map(|(obj, _)| obj.device());
+            if let Some(device) = device {
+                f(device.address, !device.trusted);
+            }
+        });
+    }
+
+    /// Calls `f` with the requested block state when the user picks Blocked.
+    pub fn connect_blocked_toggled<F: Fn(Address, bool) + 'static>(&self, f: F) {
+        let bound = self.bound.clone();
+        self.blocked_action.connect_activate(move |_, _| {
+            let device = bound.borrow().as_ref().map(|(obj, _)| obj.device());
+            if let Some(device) = device {
+                f(device.address, !device.blocked);
+            }
+        });
+    }
+
+    pub fn connect_rename<F: Fn(Address) + 'static>(&self, f: F) {
+        let bound = self.bound.clone();
+        self.rename_action.connect_activate(move |_, _| {
+            let address = bound.borrow().as_ref().map(|(obj, _)| obj.device().address);
+            if let Some(address) = address {
+                f(address);
+            }
+        });
+    }
+
+    pub fn connect_details<F: Fn(Address) + 'static>(&self, f: F) {
+        let bound = self.bound.clone();
+        self.details_action.connect_activate(move |_, _| {
+            let address = bound.borrow().as_ref().map(|(obj, _)| obj.device().address);
+            if let Some(address) = address {
+                f(address);
+            }
+        });
+    }
+
+    pub fn connect_forget<F: Fn(Address) + 'static>(&self, f: F) {
+        let bound = self.bound.clone();
+        self.forget_action.connect_activate(move |_, _| {
+            let address = bound.borrow().as_ref().map(|(obj, _)| obj.device().address);
+            if let Some(address) = address {
+                f(address);
+            }
+        });
+    }
+
+    fn update(&self, object: &DeviceObject) {
+        let device = object.device();
+        self.icon.set_icon_name(Some(&device.get_icon_name()));
+        let name = privacy::mask_name(&device.name, &device.address);
+        self.name_label.set_markup(&format!("<b>{}</b>", glib::markup_escape_text(&name)));
+        self.name_label.set_tooltip_text(name_tooltip(&device).as_deref());
+        if self.show_address {
+            self.addr_label.set_label(&privacy::mask_address(&device.address));
+        }
+
+        let battery = device.battery.filter(|_| self.show_battery);
+        if let (Some(level), Some(icon)) = (battery, device.battery_icon_name()) {
+            self.battery_icon.set_icon_name(Some(&icon));
+            self.battery_label.set_label(&format!("{}%", level));
+        }
+        self.battery_icon.set_visible(battery.is_some());
+        self.battery_label.set_visible(battery.is_some());
+
+        self.pin_button.set_active(object.is_pinned());
+        self.pin_button
+            .set_tooltip_text(Some(if object.is_pinned() { "Unpin" } else { "Pin to top" }));
+
+        let busy = object.is_busy();
+        self.spinner.set_visible(busy);
+        self.spinner.set_spinning(busy);
+
+        self.connect_switch.set_visible(device.paired);
+        // Left as the user flipped it while busy; afterwards it settles on
+        // the device's state, which also puts it back after a failure
+        if !busy {
+            self.connect_switch.set_active(device.connected);
+            self.connect_switch.set_state(device.connected);
+        }
+        self.connect_switch.set_sensitive(!busy);
+        self.pair_button.set_visible(!device.paired);
+        self.pair_button.set_sensitive(!busy);
+
+        // The menu only reflects the device; activating it asks the window
+        self.trusted_action.set_state(&device.trusted.to_variant());
+        self.blocked_action.set_state(&device.blocked.to_variant());
+        for action in [&self.trusted_action, &self.blocked_action, &self.rename_action, &self.forget_action] {
+            action.set_enabled(!busy);
+        }
+    }
+}
+
+/// Shows the remote name next to an alias, so renamed devices can still be told apart.
+fn name_tooltip(device: &BluetoothDevice) -> Option<String> {
+    match &device.remote_name {
+        Some(remote) if *remote != device.name => Some(format!("Alias: {}\nDevice name: {}", device.name, remote)),
+        Some(remote) => Some(format!("Device name: {}", remote)),
+        None => None,
+    }
+}
@@ -0,0 +1,114 @@
+use gtk4::prelude::*;
+use gtk4::{Align, Box, Button, Image, Label, Orientation, Revealer, RevealerTransitionType};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::bluetooth::BluetoothError;
+use crate::privacy;
+
+/// How long a failure stays on screen unless it is dismissed first.
+const SHOW_FOR: Duration = Duration::from_secs(6);
+
+/// Inline strip under the header that reports a failed action and what to
+/// try next, then slides away by itself.
+#[derive(Clone)]
+pub struct ErrorBanner {
+    pub root: Revealer,
+    title: Label,
+    action: Label,
+    hide_timeout: Rc<RefCell<Option<glib::SourceId>>>,
+}
+
+impl ErrorBanner {
+    pub fn new() -> Self {
+        let root = Revealer::builder()
+            .transition_type(RevealerTransitionType::SlideDown)
+            .reveal_child(false)
+            .build();
+
+        let content = Box::builder()
+            .orientation(Orientation::Horizontal)
+            .spacing(8)
+            .margin_top(4)
+            .margin_bottom(4)
+            .css_classes(vec!["error-banner"])
+            .build();
+
+        let icon = Image::builder()
+            .icon_name("dialog-warning-symbolic")
+            .pixel_size(16)
+            .valign(Align::Center)
+            .build();
+        content.append(&icon);
+
+        let text = Box::builder()
+            .orientation(Orientation::Vertical)
+            .spacing(1)
+            .hexpand(true)
+            .build();
+
+        let title = Label::builder()
+            .use_markup(true)
+            .wrap(true)
+            .xalign(0.0)
+            .build();
+        text.append(&title);
+
+        let action = Label::builder()
+            .wrap(true)
+            .xalign(0.0)
+            .css_classes(vec!["dim-label"])
+            .build();
+        text.append(&action);
+        content.append(&text);
+
+        let close_button = Button::builder()
+            .icon_name("window-close-symbolic")
+            .tooltip_text("Dismiss")
+            .css_classes(vec!["flat"])
+            .valign(Align::Center)
+            .build();
+        content.append(&close_button);
+
+        root.set_child(Some(&content));
+
+        let banner = Self {
+            root,
+            title,
+            action,
+            hide_timeout: Rc::new(RefCell::new(None)),
+        };
+
+        let b = banner.clone();
+        close_button.connect_clicked(move |_| b.hide());
+
+        banner
+    }
+
+    /// Shows that `what` (markup, e.g. "Could not connect <b>Headphones</b>")
+    /// failed with `error`, replacing any earlier failure.
+    pub fn show(&self, what: &str, error: &BluetoothError) {
+        self.title
+            .set_markup(&format!("{}: {}", what, glib::markup_escape_text(&privacy::mask_text(error.message()))));
+        self.action.set_label(error.action().unwrap_or_default());
+        self.action.set_visible(error.action().is_some());
+        self.root.set_reveal_child(true);
+
+        let banner = self.clone();
+        let timeout = glib::timeout_add_local_once(SHOW_FOR, move || {
+            banner.hide_timeout.replace(None);
+            banner.root.set_reveal_child(false);
+        });
+        if let Some(old) = self.hide_timeout.replace(Some(timeout)) {
+            old.remove();
+        }
+    }
+
+    pub fn hide(&self) {
+        if let Some(timeout) = self.hide_timeout.take() {
+            timeout.remove();
+        }
+        self.root.set_reveal_child(false);
+    }
+}
@@ -0,0 +1,126 @@
+use gtk4::prelude::*;
+use gtk4::{gio, Application, ApplicationWindow};
+use std::cell::{Cell, OnceCell, RefCell};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use super::window::Window;
+use crate::bluetooth::BluetoothService;
+use crate::config::Config;
+
+/// How long after the popup was hidden on focus loss a toggle still counts
+/// as hiding it: clicking the bar button takes focus from the popup before
+/// the launch it starts reaches us.
+const TOGGLE_GRACE: Duration = Duration::from_millis(500);
+
+thread_local! {
+    static FOCUS_CLOSED_AT: Cell<Option<Instant>> = const { Cell::new(None) };
+    static POWER_POLICY_APPLIED: Cell<bool> = const { Cell::new(false) };
+    static CONFIG: OnceCell<Rc<RefCell<Config>>> = const { OnceCell::new() };
+    static SERVICE: OnceCell<BluetoothService> = const { OnceCell::new() };
+    static BACKGROUND: OnceCell<gio::ApplicationHoldGuard> = const { OnceCell::new() };
+}
+
+/// The process's config, loaded once and shared by the window and the
+/// D-Bus API, so pins changed in the window are what `GetDevices` sorts by.
+pub fn config() -> Rc<RefCell<Config>> {
+    CONFIG.with(|config| config.get_or_init(|| Rc::new(RefCell::new(Config::load()))).clone())
+}
+
+/// The process's one `BluetoothService`, shared by every window and the
+/// D-Bus API so they agree on the adapter and the pairing agent.
+pub fn service() -> BluetoothService {
+    SERVICE.with(|service| service.get_or_init(|| BluetoothService::from_config(&config().borrow())).clone())
+}
+
+/// What a launch asks of the running widget. Every launch is forwarded to
+/// the first running instance, so there is never more than one window.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WindowRequest {
+    /// Open the window, or hide it if it is open. What a bare launch does.
+    Toggle,
+    Show,
+    Hide,
+    /// Keep running without a window, e.g. to serve the D-Bus API.
+    Background,
+}
+
+impl WindowRequest {
+    /// `None` if `args` (without the program name) name a headless command instead.
+    pub fn parse(args: &[String]) -> Option<Self> {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        match args.as_slice() {
+            [] | ["toggle"] => Some(Self::Toggle),
+            ["show"] => Some(Self::Show),
+            ["hide"] => Some(Self::Hide),
+            ["background"] => Some(Self::Background),
+            _ => None,
+        }
+    }
+}
+
+/// Carries out `request` in the primary instance. The window is built on
+/// the first request that shows it and only hidden after that, so reopening
+/// keeps its state.
+pub fn handle(app: &Application, request: WindowRequest) {
+    let window = app.windows().into_iter().find_map(|window| window.downcast::<ApplicationWindow>().ok());
+    match (request, window) {
+        (WindowRequest::Toggle | WindowRequest::Hide, Some(window)) if window.is_visible() => window.set_visible(false),
+        (WindowRequest::Hide, _) => {}
+        (WindowRequest::Toggle, _) if recently_closed_on_focus_loss() => {}
+        (WindowRequest::Show | WindowRequest::Toggle, Some(window)) => window.present(),
+        (WindowRequest::Show | WindowRequest::Toggle, None) => Window::new(app).window.present(),
+        (WindowRequest::Background, _) => BACKGROUND.with(|hold| {
+            hold.get_or_init(|| app.hold());
+        }),
+    }
+}
+
+/// Records that the window was hidden because it lost focus, so the toggle
+/// from the same click doesn't open it straight back up.
+pub fn closed_on_focus_loss() {
+    FOCUS_CLOSED_AT.with(|closed_at| closed_at.set(Some(Instant::now())));
+}
+
+/// `true` the first time it is called in this process. The startup power
+/// policy runs only then, not again when the window reopens or bluetoothd
+/// restarts.
+pub fn claim_power_policy() -> bool {
+    !POWER_POLICY_APPLIED.with(|applied| applied.replace(true))
+}
+
+fn recently_closed_on_focus_loss() -> bool {
+    FOCUS_CLOSED_AT.with(|closed_at| closed_at.take()).is_some_and(|at| at.elapsed() < TOGGLE_GRACE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Option<WindowRequest> {
+        let args: Vec<String> = line.split_whitespace().map(str::to_string).collect();
+        WindowRequest::parse(&args)
+    }
+
+    #[test]
+    fn parses_window_requests() {
+        assert_eq!(parse(""), Some(WindowRequest::Toggle));
+        assert_eq!(parse("toggle"), Some(WindowRequest::Toggle));
+        assert_eq!(parse("show"), Some(WindowRequest::Show));
+        assert_eq!(parse("hide"), Some(WindowRequest::Hide));
+        assert_eq!(parse("background"), Some(WindowRequest::Background));
+    }
+
+    #[test]
+    fn leaves_other_arguments_to_the_cli() {
+        assert_eq!(parse("list"), None);
+        assert_eq!(parse("show now"), None);
+        assert_eq!(parse("--json"), None);
+    }
+
+    #[test]
+    fn power_policy_is_claimed_once() {
+        assert!(claim_power_policy());
+        assert!(!claim_power_policy());
+    }
+}
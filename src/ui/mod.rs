@@ -1,2 +1,8 @@
+pub mod details_dialog;
+pub mod device_object;
 pub mod device_row;
+pub mod error_banner;
+pub mod instance;
+pub mod pairing_dialog;
+pub mod rename_dialog;
 pub mod window;
\ No newline at end of file
@@ -0,0 +1,155 @@
+use gtk4::prelude::*;
+use gtk4::{Align, Box, Button, Entry, InputPurpose, Label, Orientation, Window};
+use futures::channel::oneshot;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::bluetooth::{PairingReply, PAIRING_TIMEOUT};
+
+/// A small modal window for one pairing prompt.
+///
+/// It is transient for the widget, so the widget can tell that focus moved to
+/// one of its own prompts and stay open.
+#[derive(Clone)]
+pub struct PairingDialog {
+    pub window: Window,
+    message: Label,
+    pub entry: Entry,
+    expiry: Label,
+    buttons: Box,
+}
+
+impl PairingDialog {
+    pub fn new(parent: &impl IsA<Window>, title: &str) -> Self {
+        let window = Window::builder()
+            .title(title)
+            .transient_for(parent)
+            .modal(true)
+            .destroy_with_parent(true)
+            .resizable(false)
+            .build();
+
+        let content = Box::builder()
+            .orientation(Orientation::Vertical)
+            .spacing(12)
+            .margin_start(16)
+            .margin_end(16)
+            .margin_top(16)
+            .margin_bottom(16)
+            .build();
+
+        let message = Label::builder()
+            .use_markup(true)
+            .wrap(true)
+            .max_width_chars(36)
+            .xalign(0.0)
+            .build();
+        content.append(&message);
+
+        let entry = Entry::builder()
+            .visible(false)
+            .activates_default(true)
+            .build();
+        content.append(&entry);
+
+        let expiry = Label::builder()
+            .visible(false)
+            .xalign(0.0)
+            .css_classes(vec!["dim-label"])
+            .build();
+        content.append(&expiry);
+
+        let buttons = Box::builder()
+            .orientation(Orientation::Horizontal)
+            .spacing(6)
+            .halign(Align::End)
+            .build();
+        content.append(&buttons);
+
+        window.set_child(Some(&content));
+
+        Self {
+            window,
+            message,
+            entry,
+            expiry,
+            buttons,
+        }
+    }
+
+    pub fn set_message(&self, markup: &str) {
+        self.message.set_markup(markup);
+    }
+
+    /// Shows the entry for typing a PIN code, or a numeric passkey when `numeric`.
+    pub fn show_entry(&self, numeric: bool) {
+        if numeric {
+            self.entry.set_input_purpose(InputPurpose::Digits);
+            self.entry.set_max_length(6);
+        } else {
+            self.entry.set_max_length(16);
+        }
+        self.entry.set_visible(true);
+        self.entry.grab_focus();
+    }
+
+    pub fn add_button(&self, label: &str, suggested: bool) -> Button {
+        let button = Button::with_label(label);
+        if suggested {
+            button.add_css_class("suggested-action");
+            self.window.set_default_widget(Some(&button));
+        }
+        self.buttons.append(&button);
+        button
+    }
+
+    /// Adds Cancel and `accept_label` buttons answering `reply`. `answer`
+    /// reads the value to send and returns `None` while the input is invalid.
+    /// Closing the dialog any other way rejects the request.
+    pub fn ask<T: 'static>(
+        &self,
+        accept_label: &str,
+        reply: PairingReply<T>,
+        answer: impl Fn(&Self) -> Option<T> + 'static,
+    ) {
+        let reply = Rc::new(RefCell::new(Some(reply)));
+        self.expiry
+            .set_label(&format!("The request expires after {} seconds.", PAIRING_TIMEOUT.as_secs()));
+        self.expiry.set_visible(true);
+
+        let window = self.window.clone();
+        self.add_button("Cancel", false).connect_clicked(move |_| window.close());
+
+        let dialog = self.clone();
+        let accepted = reply.clone();
+        self.add_button(accept_label, true).connect_clicked(move |_| {
+            let Some(value) = answer(&dialog) else {
+                dialog.entry.error_bell();
+                return;
+            };
+            if let Some(reply) = accepted.borrow_mut().take() {
+                reply.accept(value);
+            }
+            dialog.window.close();
+        });
+
+        self.window.connect_close_request(move |_| {
+            if let Some(reply) = reply.borrow_mut().take() {
+                reply.reject();
+            }
+            glib::Propagation::Proceed
+        });
+    }
+
+    /// Takes the dialog down once `closed` resolves: the request was answered,
+    /// timed out or was cancelled by the remote device.
+    pub fn close_on(&self, closed: oneshot::Receiver<()>) {
+        let window = self.window.downgrade();
+        glib::spawn_future_local(async move {
+            let _ = closed.await;
+            if let Some(window) = window.upgrade() {
+                window.close();
+            }
+        });
+    }
+}
@@ -0,0 +1,99 @@
+use gtk4::prelude::*;
+use gtk4::{Align, Box, Button, Entry, Label, Orientation, Window};
+use futures::channel::oneshot;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// BlueZ keeps aliases up to 248 bytes; this keeps them readable in a row.
+const MAX_ALIAS_CHARS: i32 = 64;
+
+/// Asks for a new name for a device, prefilled with `current`.
+///
+/// Resolves to the entered name, an empty string to go back to `remote_name`,
+/// or `None` if the dialog was cancelled.
+pub async fn ask_name(parent: &impl IsA<Window>, current: &str, remote_name: Option<&str>) -> Option<String> {
+    let window = Window::builder()
+        .title("Rename Device")
+        .transient_for(parent)
+        .modal(true)
+        .destroy_with_parent(true)
+        .resizable(false)
+        .build();
+
+    let content = Box::builder()
+        .orientation(Orientation::Vertical)
+        .spacing(12)
+        .margin_start(16)
+        .margin_end(16)
+        .margin_top(16)
+        .margin_bottom(16)
+        .build();
+
+    let message = Label::builder()
+        .use_markup(true)
+        .wrap(true)
+        .max_width_chars(36)
+        .xalign(0.0)
+        .label(format!("Rename <b>{}</b>", glib::markup_escape_text(current)))
+        .build();
+    content.append(&message);
+
+    let entry = Entry::builder()
+        .text(current)
+        .max_length(MAX_ALIAS_CHARS)
+        .activates_default(true)
+        .build();
+    content.append(&entry);
+
+    if let Some(remote_name) = remote_name {
+        let hint = Label::builder()
+            .wrap(true)
+            .max_width_chars(36)
+            .xalign(0.0)
+            .css_classes(vec!["dim-label"])
+            .label(format!("The device calls itself “{}”. Leave the name empty to use that.", remote_name))
+            .build();
+        content.append(&hint);
+    }
+
+    let buttons = Box::builder()
+        .orientation(Orientation::Horizontal)
+        .spacing(6)
+        .halign(Align::End)
+        .build();
+    let cancel = Button::with_label("Cancel");
+    buttons.append(&cancel);
+    let rename = Button::builder()
+        .label("Rename")
+        .css_classes(vec!["suggested-action"])
+        .build();
+    buttons.append(&rename);
+    content.append(&buttons);
+
+    window.set_child(Some(&content));
+    window.set_default_widget(Some(&rename));
+
+    let (answer, answered) = oneshot::channel();
+    let answer = Rc::new(RefCell::new(Some(answer)));
+
+    let w = window.clone();
+    cancel.connect_clicked(move |_| w.close());
+
+    let w = window.clone();
+    let reply = answer.clone();
+    rename.connect_clicked(move |_| {
+        if let Some(reply) = reply.borrow_mut().take() {
+            let _ = reply.send(entry.text().trim().to_string());
+        }
+        w.close();
+    });
+
+    // Closing any other way cancels; dropping the sender resolves `answered`
+    window.connect_close_request(move |_| {
+        answer.borrow_mut().take();
+        glib::Propagation::Proceed
+    });
+
+    window.present();
+    answered.await.ok()
+}
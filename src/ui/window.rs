@@ -1,50 +1,116 @@
 use gtk4::prelude::*;
 use gtk4::{
-    Application, ApplicationWindow, Box, Button, CssProvider, GestureDrag, Image, Label, ListBox,
-    Orientation, ScrolledWindow, Separator, Switch, Align, STYLE_PROVIDER_PRIORITY_APPLICATION,
-    style_context_add_provider_for_display,
+    gio, AlertDialog, Application, ApplicationWindow, Box, Button, CssProvider, CustomSorter, DropDown, GestureDrag,
+    Image, Label, ListHeader, ListItem, ListView, NoSelection, Orientation, ScrolledWindow, Separator,
+    SignalListItemFactory, SortListModel, SorterChange, Spinner, StringList, Switch, ToggleButton, Align,
+    STYLE_PROVIDER_PRIORITY_APPLICATION, style_context_add_provider_for_display,
 };
-use std::sync::{Arc, Mutex, mpsc};
-use std::thread;
+use bluer::Address;
+use futures::StreamExt;
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use crate::bluetooth::BluetoothService;
-use crate::config::Config;
+use super::instance;
+use crate::bluetooth::{
+    compare_devices, AdapterInfo, BluetoothDevice, BluetoothError, BluetoothEvent, BluetoothService, PairingRequest,
+    RfkillState, ServiceState,
+};
+use crate::config::{BackendKind, Config, PinnedDevice, PowerPolicy};
+use crate::privacy;
+use crate::ui::details_dialog;
+use crate::ui::device_object::DeviceObject;
 use crate::ui::device_row::DeviceRow;
+use crate::ui::error_banner::ErrorBanner;
+use crate::ui::pairing_dialog::PairingDialog;
+use crate::ui::rename_dialog;
 
+#[derive(Clone)]
 pub struct Window {
     pub window: ApplicationWindow,
-    pub list_box: ListBox,
+    pub list_view: ListView,
     pub status_label: Label,
+    /// Shown only when more than one adapter is present.
+    pub adapter_picker: DropDown,
+    /// Lifts a software rfkill block; shown only while one is active.
+    unblock_button: Button,
     pub toggle_switch: Switch,
-    bluetooth_service: Arc<Mutex<BluetoothService>>,
-    config: Config,
+    pub scan_button: ToggleButton,
+    scan_spinner: Spinner,
+    /// Replaces the list while the backend is unavailable.
+    unavailable_box: Box,
+    unavailable_label: Label,
+    retry_button: Button,
+    /// Reports failed power, connect and pair actions.
+    error_banner: ErrorBanner,
+    bluetooth_service: BluetoothService,
+    /// Shared so pins changed from a row are seen by the sorter and saved.
+    config: Rc<RefCell<Config>>,
+    /// Lets programmatic switch updates bypass the power handler.
+    power_handler: Rc<OnceCell<glib::SignalHandlerId>>,
+    powered: Rc<Cell<bool>>,
+    rfkill: Rc<Cell<RfkillState>>,
+    /// Adapters in picker order.
+    adapters: Rc<RefCell<Vec<AdapterInfo>>>,
+    adapter_handler: Rc<OnceCell<glib::SignalHandlerId>>,
+    /// Backend event subscription; restarted when the adapter or backend changes.
+    /// Kept while the window is hidden, so it is current when shown again.
+    event_task: Rc<RefCell<Option<glib::JoinHandle<()>>>>,
+    /// Pairing agent listener; restarted when the backend is recreated.
+    pairing_task: Rc<RefCell<Option<glib::JoinHandle<()>>>>,
+    /// Periodic full resync of the device list; only runs while shown.
+    resync: Rc<RefCell<Option<glib::SourceId>>>,
+    /// Unsorted model behind the list; `devices` indexes it by address.
+    store: gio::ListStore,
+    sorter: CustomSorter,
+    /// Splits the list into "My devices" (paired) and "Available devices".
+    section_sorter: CustomSorter,
+    devices: Rc<RefCell<HashMap<Address, DeviceObject>>>,
+    scan: Rc<RefCell<Option<Scan>>>,
+    /// Open DisplayPasskey prompts, updated in place as digits are typed.
+    passkey_dialogs: Rc<RefCell<HashMap<Address, (PairingDialog, glib::JoinHandle<()>)>>>,
+}
+
+/// A running discovery; dropping the task's stream stops the adapter scan.
+struct Scan {
+    task: glib::JoinHandle<()>,
+    timeout: Option<glib::SourceId>,
 }
 
 impl Window {
     pub fn new(app: &Application) -> Self {
-        let config = Config::load();
-        let bluetooth_service = Arc::new(Mutex::new(BluetoothService::new().unwrap_or_else(|e| {
-            eprintln!("Failed to initialize Bluetooth service: {}", e);
-            // In a real app we might want to show an error dialog or exit
-            panic!("Bluetooth service init failed");
-        })));
+        Self::with_service(app, instance::config(), instance::service())
+    }
 
+    /// Builds the window around `config` and `bluetooth_service`, which it
+    /// shares with whoever else holds them; `new` passes the process's own.
+    pub fn with_service(app: &Application, config: Rc<RefCell<Config>>, bluetooth_service: BluetoothService) -> Self {
+        let (width, height) = {
+            let config = config.borrow();
+            privacy::set_mask(config.address_mask);
+            (config.window_width, config.window_height)
+        };
         let window = ApplicationWindow::builder()
             .application(app)
             .title("Bluetooth Widget")
-            .default_width(config.window_width)
-            .default_height(config.window_height)
+            .default_width(width)
+            .default_height(height)
             .resizable(true)
+            // Every way of closing it just hides it; see `instance::handle`
+            .hide_on_close(true)
             .build();
 
         let provider = CssProvider::new();
         provider.load_from_data(
             "window { background-color: rgba(0, 0, 0, 0.85); color: white; }
-             list { background-color: transparent; }
+             listview { background-color: transparent; }
              row { background-color: transparent; }
              row:hover { background-color: rgba(255, 255, 255, 0.1); }
-             .dim-label { opacity: 0.7; }"
+             .dim-label { opacity: 0.7; }
+             .error-banner { background-color: rgba(192, 28, 40, 0.35); border-radius: 6px; padding: 4px 8px; }"
         );
         
         style_context_add_provider_for_display(
@@ -84,6 +150,24 @@ impl Window {
             .build();
         header_box.append(&status_label);
 
+        // Adapter picker
+        let adapter_picker = DropDown::builder()
+            .model(&StringList::new(&[]))
+            .tooltip_text("Bluetooth adapter")
+            .visible(false)
+            .valign(Align::Center)
+            .build();
+        header_box.append(&adapter_picker);
+
+        let unblock_button = Button::builder()
+            .label("Unblock")
+            .tooltip_text("Turn off the software block (e.g. airplane mode) for Bluetooth")
+            .css_classes(vec!["flat"])
+            .visible(false)
+            .valign(Align::Center)
+            .build();
+        header_box.append(&unblock_button);
+
         // Spacer
         let spacer = Box::new(Orientation::Horizontal, 0);
         spacer.set_hexpand(true);
@@ -98,6 +182,21 @@ impl Window {
             .build();
         header_box.append(&refresh_button);
 
+        // Scan toggle
+        let scan_spinner = Spinner::builder()
+            .visible(false)
+            .valign(Align::Center)
+            .build();
+        header_box.append(&scan_spinner);
+
+        let scan_button = ToggleButton::builder()
+            .icon_name("system-search")
+            .tooltip_text("Scan for new devices")
+            .css_classes(vec!["flat"])
+            .valign(Align::Center)
+            .build();
+        header_box.append(&scan_button);
+
         // Settings button
         let settings_button = Button::builder()
             .icon_name("preferences-system")
@@ -111,12 +210,6 @@ impl Window {
         let toggle_switch = Switch::builder()
             .valign(Align::Center)
             .build();
-        
-        // Set initial state
-        if let Ok(service) = bluetooth_service.lock() {
-            toggle_switch.set_active(service.is_powered());
-        }
-        
         header_box.append(&toggle_switch);
 
         // Close button
@@ -134,76 +227,195 @@ impl Window {
         let separator = Separator::new(Orientation::Horizontal);
         main_box.append(&separator);
 
+        let error_banner = ErrorBanner::new();
+        main_box.append(&error_banner.root);
+
+        // Shown instead of the list when bluetoothd or the simulation can't be reached
+        let unavailable_box = Box::builder()
+            .orientation(Orientation::Vertical)
+            .spacing(6)
+            .margin_top(12)
+            .margin_bottom(12)
+            .valign(Align::Center)
+            .vexpand(true)
+            .visible(false)
+            .build();
+
+        let unavailable_icon = Image::builder()
+            .icon_name("bluetooth-disabled")
+            .pixel_size(32)
+            .css_classes(vec!["dim-label"])
+            .build();
+        unavailable_box.append(&unavailable_icon);
+
+        let unavailable_title = Label::builder()
+            .use_markup(true)
+            .label("<b>Bluetooth is unavailable</b>")
+            .build();
+        unavailable_box.append(&unavailable_title);
+
+        let unavailable_label = Label::builder()
+            .wrap(true)
+            .justify(gtk4::Justification::Center)
+            .css_classes(vec!["dim-label"])
+            .build();
+        unavailable_box.append(&unavailable_label);
+
+        let retry_button = Button::builder()
+            .label("Retry")
+            .halign(Align::Center)
+            .build();
+        unavailable_box.append(&retry_button);
+
+        main_box.append(&unavailable_box);
+
         // Device list
         let scrolled = ScrolledWindow::builder()
             .min_content_height(0)
+            .propagate_natural_height(true)
+            .vexpand(true)
             .hscrollbar_policy(gtk4::PolicyType::Never)
-            .vscrollbar_policy(gtk4::PolicyType::Never)
+            .vscrollbar_policy(gtk4::PolicyType::Automatic)
             .build();
 
-        let list_box = ListBox::builder()
-            .selection_mode(gtk4::SelectionMode::None)
-            .build();
-        
-        scrolled.set_child(Some(&list_box));
+        let store = gio::ListStore::new::<DeviceObject>();
+        let pins = config.clone();
+        let sorter = CustomSorter::new(move |a, b| {
+            let a = a.downcast_ref::<DeviceObject>().expect("store only holds DeviceObject").device();
+            let b = b.downcast_ref::<DeviceObject>().expect("store only holds DeviceObject").device();
+            compare_devices(&a, &b, &pins.borrow().pinned_devices).into()
+        });
+        let section_sorter = CustomSorter::new(|a, b| {
+            let a = a.downcast_ref::<DeviceObject>().expect("store only holds DeviceObject").device();
+            let b = b.downcast_ref::<DeviceObject>().expect("store only holds DeviceObject").device();
+            b.paired.cmp(&a.paired).into()
+        });
+        let sorted = SortListModel::new(Some(store.clone()), Some(sorter.clone()));
+        sorted.set_section_sorter(Some(&section_sorter));
+        let list_view = ListView::new(Some(NoSelection::new(Some(sorted))), None::<SignalListItemFactory>);
+        list_view.set_header_factory(Some(&section_header_factory()));
+
+        scrolled.set_child(Some(&list_view));
         main_box.append(&scrolled);
 
         window.set_child(Some(&main_box));
 
         let win = Self {
             window,
-            list_box,
+            list_view,
             status_label,
+            adapter_picker,
+            unblock_button,
             toggle_switch,
+            scan_button,
+            scan_spinner,
+            unavailable_box,
+            unavailable_label,
+            retry_button,
+            error_banner,
             bluetooth_service,
             config,
+            power_handler: Rc::new(OnceCell::new()),
+            powered: Rc::new(Cell::new(false)),
+            rfkill: Rc::new(Cell::new(RfkillState::default())),
+            adapters: Rc::new(RefCell::new(Vec::new())),
+            adapter_handler: Rc::new(OnceCell::new()),
+            event_task: Rc::new(RefCell::new(None)),
+            pairing_task: Rc::new(RefCell::new(None)),
+            resync: Rc::new(RefCell::new(None)),
+            store,
+            sorter,
+            section_sorter,
+            devices: Rc::new(RefCell::new(HashMap::new())),
+            scan: Rc::new(RefCell::new(None)),
+            passkey_dialogs: Rc::new(RefCell::new(HashMap::new())),
         };
 
+        win.list_view.set_factory(Some(&win.device_row_factory()));
         win.setup_signals(refresh_button, settings_button, close_button);
         win.setup_gestures();
-        win.refresh_devices();
+        win.watch_events();
+        win.watch_rfkill();
+        win.watch_service_state();
 
         win
     }
 
     fn setup_signals(&self, refresh_btn: Button, settings_btn: Button, close_btn: Button) {
-        let service = self.bluetooth_service.clone();
-        let status_label = self.status_label.clone();
-        let bt_enabled = self.config.enable_bluetooth_functionality;
-
         // Toggle Bluetooth
-        self.toggle_switch.connect_state_set(move |_, state| {
-            if bt_enabled {
-                if let Ok(service) = service.lock() {
-                    if state {
-                        let _ = service.power_on();
-                        status_label.set_markup("<b>Bluetooth</b> <span foreground='green'>On</span>");
-                    } else {
-                        let _ = service.power_off();
-                        status_label.set_markup("<b>Bluetooth</b> <span foreground='red'>Off</span>");
+        let win = self.clone();
+        let handler = self.toggle_switch.connect_state_set(move |_, state| {
+            let win = win.clone();
+            glib::spawn_future_local(async move {
+                // Turning on a soft-blocked radio lifts the block first
+                match win.bluetooth_service.set_powered(state).await {
+                    Ok(()) => {
+                        let mut config = win.config.borrow_mut();
+                        config.last_powered = Some(state);
+                        if let Err(e) = config.save() {
+                            eprintln!("Failed to save power state: {}", e);
+                        }
                     }
+                    Err(e) => win.show_error(
+                        if state { "Could not turn Bluetooth on" } else { "Could not turn Bluetooth off" },
+                        &e,
+                    ),
                 }
-            } else {
-                // UI testing mode - just update the label without actually changing bluetooth
-                if state {
-                    status_label.set_markup("<b>Bluetooth</b> <span foreground='green'>On</span> <span foreground='orange'>(UI Test)</span>");
-                } else {
-                    status_label.set_markup("<b>Bluetooth</b> <span foreground='red'>Off</span> <span foreground='orange'>(UI Test)</span>");
-                }
-            }
-            // Trigger refresh
-            // In a real app we'd want to trigger this on the main struct
+                // Show what the adapter really did, e.g. stay off when blocked
+                win.read_power_state().await;
+            });
             glib::Propagation::Proceed
         });
+        let _ = self.power_handler.set(handler);
+
+        // Adapter picker
+        let win = self.clone();
+        let handler = self.adapter_picker.connect_selected_notify(move |picker| {
+            let name = win.adapters.borrow().get(picker.selected() as usize).map(|a| a.name.clone());
+            if let Some(name) = name {
+                win.switch_adapter(name, true);
+            }
+        });
+        let _ = self.adapter_handler.set(handler);
+
+        // Lift a software block
+        let win = self.clone();
+        self.unblock_button.connect_clicked(move |_| {
+            // The rfkill watch picks up the change
+            if let Err(e) = win.bluetooth_service.unblock_rfkill() {
+                win.show_error("Could not unblock Bluetooth", &e.into());
+            }
+        });
+
+        // Retry after the backend was unavailable
+        let win = self.clone();
+        self.retry_button.connect_clicked(move |_| {
+            let win = win.clone();
+            glib::spawn_future_local(async move {
+                // Success shows up as a Ready state; failure updates the reason.
+                let _ = win.bluetooth_service.retry().await;
+            });
+        });
 
         // Refresh button
-        refresh_btn.connect_clicked(|_| {
-             // We need a way to call refresh_devices here. 
-             // For simplicity in this structure, we might need to rethink ownership or use channels.
-             // For now, let's just print.
-             println!("Refresh clicked");
+        let win = self.clone();
+        refresh_btn.connect_clicked(move |_| {
+            win.refresh_devices();
+        });
+
+        // Scan toggle
+        let win = self.clone();
+        self.scan_button.connect_toggled(move |button| {
+            if button.is_active() {
+                win.start_scan();
+            } else {
+                win.stop_scan();
+            }
         });
 
+        let win = self.clone();
+        self.window.connect_hide(move |_| win.stop_scan());
+
         // Close button
         let window_weak_close = self.window.downgrade();
         close_btn.connect_clicked(move |_| {
@@ -221,12 +433,17 @@ impl Window {
         // Close on focus lost
         self.window.connect_is_active_notify(|win| {
             if !win.is_active() {
-                println!("Window lost focus - closing");
                 // Delay close slightly to allow clicks to register
                 let win_weak = win.downgrade();
                 glib::timeout_add_local(Duration::from_millis(50), move || {
                     if let Some(win) = win_weak.upgrade() {
-                        win.close();
+                        // Focus moving to one of our pairing prompts isn't "lost",
+                        // and hiding the window on request takes focus too
+                        if win.is_visible() && !has_active_dialog(&win) {
+                            println!("Window lost focus - closing");
+                            instance::closed_on_focus_loss();
+                            win.close();
+                        }
                     }
                     glib::ControlFlow::Break
                 });
@@ -270,80 +487,826 @@ impl Window {
         self.window.add_controller(gesture);
     }
 
-    pub fn refresh_devices(&self) {
-        // Clear list
-        while let Some(child) = self.list_box.first_child() {
-            self.list_box.remove(&child);
+    /// Resyncs the full device list every `refresh_interval` ms while the
+    /// window is shown, as a fallback for missed signals. The subscription
+    /// itself starts whenever the service becomes ready.
+    fn watch_events(&self) {
+        let win = self.clone();
+        self.window.connect_show(move |_| win.start_resync());
+        let win = self.clone();
+        self.window.connect_hide(move |_| win.stop_resync());
+    }
+
+    fn start_resync(&self) {
+        let refresh_interval = self.config.borrow().refresh_interval;
+        if refresh_interval == 0 {
+            return;
+        }
+        let win = self.clone();
+        let source = glib::timeout_add_local(Duration::from_millis(refresh_interval), move || {
+            win.refresh_devices();
+            glib::ControlFlow::Continue
+        });
+        if let Some(old) = self.resync.replace(Some(source)) {
+            old.remove();
         }
+    }
 
-        let service = self.bluetooth_service.clone();
-        let list_box = self.list_box.clone();
-        let service_clone = self.bluetooth_service.clone();
-        let bt_enabled = self.config.enable_bluetooth_functionality;
+    fn stop_resync(&self) {
+        if let Some(source) = self.resync.take() {
+            source.remove();
+        }
+    }
 
-        // Use channel to send devices from thread to main thread
-        let (tx, rx) = mpsc::channel();
+    /// (Re)subscribes to backend events, replacing any earlier subscription.
+    fn subscribe_events(&self) {
+        let win = self.clone();
+        let task = glib::spawn_future_local(async move {
+            let mut events = match win.bluetooth_service.subscribe().await {
+                Ok(events) => events,
+                Err(e) => {
+                    eprintln!("Failed to subscribe to Bluetooth events: {}", e);
+                    return;
+                }
+            };
 
-        // Spawn thread to fetch devices (without moving GTK widgets)
-        let service_for_thread = service.clone();
-        thread::spawn(move || {
-            let devices = if let Ok(s) = service_for_thread.lock() {
-                s.get_devices()
-            } else {
-                vec![]
+            while let Some(event) = events.next().await {
+                win.handle_event(event);
+            }
+        });
+
+        if let Some(old) = self.event_task.replace(Some(task)) {
+            old.abort();
+        }
+    }
+
+    /// Reloads the adapter list and picker, and falls back to another
+    /// adapter if the selected one has gone away.
+    fn refresh_adapters(&self) {
+        let win = self.clone();
+        glib::spawn_future_local(async move {
+            let service = &win.bluetooth_service;
+            let (adapters, current) = match (service.adapters().await, service.current_adapter().await) {
+                (Ok(adapters), Ok(current)) => (adapters, current),
+                (Err(e), _) | (_, Err(e)) => {
+                    eprintln!("Failed to list adapters: {}", e);
+                    return;
+                }
             };
+            win.show_adapters(adapters.clone(), current.as_deref());
+
+            if current.is_some() {
+                return;
+            }
+            // Prefer the configured adapter, then whatever is left.
+            let preferred = win.config.borrow().adapter.clone();
+            let fallback = adapters
+                .iter()
+                .find(|a| Some(&a.name) == preferred.as_ref())
+                .or_else(|| adapters.first());
+            match fallback {
+                Some(adapter) => win.switch_adapter(adapter.name.clone(), false),
+                None => win.show_no_adapter(),
+            }
+        });
+    }
+
+    fn show_adapters(&self, adapters: Vec<AdapterInfo>, current: Option<&str>) {
+        let labels: Vec<String> = adapters
+            .iter()
+            .map(|a| format!("{} ({})", a.alias, a.name))
+            .collect();
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+        let selected = adapters.iter().position(|a| Some(a.name.as_str()) == current);
 
-            let _ = tx.send(devices);
+        if let Some(handler) = self.adapter_handler.get() {
+            self.adapter_picker.block_signal(handler);
+            self.adapter_picker.set_model(Some(&StringList::new(&labels)));
+            self.adapter_picker
+                .set_selected(selected.map_or(gtk4::INVALID_LIST_POSITION, |i| i as u32));
+            self.adapter_picker.unblock_signal(handler);
+        }
+        self.adapter_picker.set_visible(adapters.len() > 1);
+        self.adapters.replace(adapters);
+    }
+
+    /// Moves the widget to adapter `name`: stops any scan, reloads the
+    /// device list and follows the new adapter's events. `remember` saves it
+    /// as the preferred adapter; automatic fallbacks don't.
+    fn switch_adapter(&self, name: String, remember: bool) {
+        let win = self.clone();
+        glib::spawn_future_local(async move {
+            win.scan_button.set_active(false);
+            if let Err(e) = win.bluetooth_service.select_adapter(name.clone()).await {
+                eprintln!("Failed to select adapter {}: {}", name, e);
+                win.refresh_adapters();
+                return;
+            }
+
+            if remember {
+                let mut config = win.config.borrow_mut();
+                config.adapter = Some(name);
+                if let Err(e) = config.save() {
+                    eprintln!("Failed to save adapter choice: {}", e);
+                }
+            }
+
+            win.toggle_switch.set_sensitive(!win.rfkill.get().hard);
+            win.scan_button.set_sensitive(true);
+            win.apply_snapshot(Vec::new());
+            win.subscribe_events();
+            win.refresh_adapters();
+            win.sync_power_state();
+            win.refresh_devices();
         });
+    }
+
+    fn show_no_adapter(&self) {
+        self.scan_button.set_active(false);
+        self.apply_snapshot(Vec::new());
+        self.toggle_switch.set_sensitive(false);
+        self.scan_button.set_sensitive(false);
+        self.status_label
+            .set_markup("<b>Bluetooth</b> <span foreground='gray'>No adapter</span>");
+    }
+
+    /// Answers the backend's pairing agent with dialogs for as long as the window is open.
+    fn watch_pairing_requests(&self) {
+        let win = self.clone();
+        let task = glib::spawn_future_local(async move {
+            let mut requests = match win.bluetooth_service.pairing_requests().await {
+                Ok(requests) => requests,
+                Err(e) => {
+                    eprintln!("Failed to listen for pairing requests: {}", e);
+                    return;
+                }
+            };
 
-        // Receive devices on main thread and update UI
-        let list_box_clone = list_box.clone();
-        glib::idle_add_local(move || {
-            if let Ok(devices) = rx.try_recv() {
-                for device in devices.iter() {
-                    let row_widget = DeviceRow::new(device);
-
-                    // Connect signals for row
-                    if let Some(switch) = &row_widget.connect_switch {
-                        let s = service_clone.clone();
-                        let addr = device.address;
-                        switch.connect_state_set(move |_, state| {
-                            if bt_enabled {
-                                if let Ok(service) = s.lock() {
-                                    if state {
-                                        let _ = service.connect_device(addr);
-                                    } else {
-                                        let _ = service.disconnect_device(addr);
-                                    }
-                                }
-                            } else {
-                                println!("UI Test Mode: Would {} device {}", if state { "connect" } else { "disconnect" }, addr);
-                            }
-                            glib::Propagation::Proceed
+            while let Some(request) = requests.next().await {
+                win.show_pairing_request(request);
+            }
+        });
+
+        if let Some(old) = self.pairing_task.replace(Some(task)) {
+            old.abort();
+        }
+    }
+
+    /// Follows the service between ready and unavailable. Each time it
+    /// becomes ready (at startup, after a retry or once bluetoothd appears)
+    /// the window re-attaches to the new backend.
+    fn watch_service_state(&self) {
+        let win = self.clone();
+        glib::spawn_future_local(async move {
+            let mut states = win.bluetooth_service.watch_state();
+            while let Some(state) = states.next().await {
+                win.show_service_state(state);
+            }
+        });
+    }
+
+    fn show_service_state(&self, state: ServiceState) {
+        match state {
+            ServiceState::Connecting => {
+                self.retry_button.set_sensitive(false);
+            }
+            ServiceState::Ready => {
+                self.unavailable_box.set_visible(false);
+                self.toggle_switch.set_sensitive(!self.rfkill.get().hard);
+                self.scan_button.set_sensitive(true);
+
+                self.subscribe_events();
+                self.watch_pairing_requests();
+                self.refresh_adapters();
+                if instance::claim_power_policy() {
+                    self.apply_power_policy();
+                } else {
+                    self.sync_power_state();
+                }
+                self.refresh_devices();
+            }
+            ServiceState::Unavailable(reason) => {
+                self.scan_button.set_active(false);
+                self.apply_snapshot(Vec::new());
+                self.toggle_switch.set_sensitive(false);
+                self.scan_button.set_sensitive(false);
+                self.adapter_picker.set_visible(false);
+                self.status_label
+                    .set_markup("<b>Bluetooth</b> <span foreground='gray'>Unavailable</span>");
+
+                self.unavailable_label.set_label(&reason);
+                self.retry_button.set_sensitive(true);
+                self.unavailable_box.set_visible(true);
+            }
+        }
+    }
+
+    fn show_pairing_request(&self, request: PairingRequest) {
+        match request {
+            PairingRequest::PinCode { device, reply, closed } => {
+                let dialog = PairingDialog::new(&self.window, "Pairing Request");
+                dialog.set_message(&format!(
+                    "Enter the PIN code for <b>{}</b>.",
+                    self.device_name(device)
+                ));
+                dialog.show_entry(false);
+                dialog.ask("Pair", reply, |dialog| {
+                    let pin = dialog.entry.text().trim().to_string();
+                    (!pin.is_empty()).then_some(pin)
+                });
+                dialog.close_on(closed);
+                dialog.window.present();
+            }
+            PairingRequest::Passkey { device, reply, closed } => {
+                let dialog = PairingDialog::new(&self.window, "Pairing Request");
+                dialog.set_message(&format!(
+                    "Enter the passkey shown on <b>{}</b>.",
+                    self.device_name(device)
+                ));
+                dialog.show_entry(true);
+                dialog.ask("Pair", reply, |dialog| {
+                    dialog.entry.text().trim().parse::<u32>().ok().filter(|p| *p <= 999_999)
+                });
+                dialog.close_on(closed);
+                dialog.window.present();
+            }
+            PairingRequest::DisplayPasskey { device, passkey, entered, closed } => {
+                let message = format!(
+                    "Type <tt><big><b>{:06}</b></big></tt> on <b>{}</b>, then press Enter.\n\n{} of 6 digits typed.",
+                    passkey,
+                    self.device_name(device),
+                    entered
+                );
+
+                // bluetoothd re-sends this request as digits are typed; keep one dialog per device.
+                let existing = self.passkey_dialogs.borrow_mut().remove(&device);
+                let dialog = match existing {
+                    Some((dialog, watcher)) => {
+                        watcher.abort();
+                        dialog
+                    }
+                    None => {
+                        let dialog = PairingDialog::new(&self.window, "Pairing Request");
+                        let window = dialog.window.clone();
+                        dialog.add_button("Close", false).connect_clicked(move |_| window.close());
+                        let dialogs = self.passkey_dialogs.clone();
+                        dialog.window.connect_destroy(move |_| {
+                            dialogs.borrow_mut().remove(&device);
                         });
+                        dialog.window.present();
+                        dialog
                     }
+                };
+                dialog.set_message(&message);
 
-                    if let Some(button) = &row_widget.pair_button {
-                        let s = service_clone.clone();
-                        let addr = device.address;
-                        button.connect_clicked(move |_| {
-                            if bt_enabled {
-                                if let Ok(service) = s.lock() {
-                                    let _ = service.pair_device(addr);
-                                }
-                            } else {
-                                println!("UI Test Mode: Would pair device {}", addr);
-                            }
+                let window = dialog.window.downgrade();
+                let watcher = glib::spawn_future_local(async move {
+                    let _ = closed.await;
+                    if let Some(window) = window.upgrade() {
+                        window.close();
+                    }
+                });
+                self.passkey_dialogs.borrow_mut().insert(device, (dialog, watcher));
+            }
+            PairingRequest::Confirmation { device, passkey, reply, closed } => {
+                let dialog = PairingDialog::new(&self.window, "Confirm Pairing");
+                dialog.set_message(&format!(
+                    "Does <b>{}</b> show the passkey <tt><big><b>{:06}</b></big></tt>?",
+                    self.device_name(device),
+                    passkey
+                ));
+                dialog.ask("Pair", reply, |_| Some(()));
+                dialog.close_on(closed);
+                dialog.window.present();
+            }
+            PairingRequest::AuthorizeService { device, service, reply, closed } => {
+                let dialog = PairingDialog::new(&self.window, "Authorize Service");
+                dialog.set_message(&format!(
+                    "Allow <b>{}</b> to use service <tt>{}</tt>?",
+                    self.device_name(device),
+                    glib::markup_escape_text(&service)
+                ));
+                dialog.ask("Allow", reply, |_| Some(()));
+                dialog.close_on(closed);
+                dialog.window.present();
+            }
+        }
+    }
+
+    /// Markup-escaped name for prompts, falling back to the address for devices not listed yet
+    /// or without a name.
+    fn device_name(&self, address: Address) -> glib::GString {
+        glib::markup_escape_text(&self.plain_device_name(address))
+    }
+
+    fn plain_device_name(&self, address: Address) -> String {
+        let name = self.devices.borrow().get(&address).map(|obj| obj.device().name).unwrap_or_default();
+        privacy::mask_name(&name, &address)
+    }
+
+    fn handle_event(&self, event: BluetoothEvent) {
+        match event {
+            BluetoothEvent::PowerChanged(powered) => self.show_power_state(powered),
+            BluetoothEvent::DeviceAdded(device) | BluetoothEvent::DeviceChanged(device) => {
+                self.upsert_device(device)
+            }
+            BluetoothEvent::DeviceRemoved(address) => self.remove_device(address),
+            BluetoothEvent::AdapterAdded(adapter) => {
+                // Follow the preferred adapter back when it is plugged in again
+                let preferred = self.config.borrow().adapter.clone();
+                if preferred.as_ref() == Some(&adapter.name) {
+                    self.switch_adapter(adapter.name, false);
+                } else {
+                    self.refresh_adapters();
+                }
+            }
+            BluetoothEvent::AdapterRemoved(_) => self.refresh_adapters(),
+        }
+    }
+
+    /// Runs adapter discovery until the scan button is released, the window
+    /// closes or `discovery_timeout` elapses. Found devices land in the
+    /// "Available devices" section.
+    fn start_scan(&self) {
+        let win = self.clone();
+        let task = glib::spawn_future_local(async move {
+            match win.bluetooth_service.discover().await {
+                Ok(mut found) => {
+                    while let Some(event) = found.next().await {
+                        win.handle_event(event);
+                    }
+                }
+                Err(e) => win.show_error("Could not search for devices", &e.into()),
+            }
+            // Discovery failed or was stopped outside the widget
+            win.scan_button.set_active(false);
+        });
+
+        let discovery_timeout = self.config.borrow().discovery_timeout;
+        let timeout = (discovery_timeout > 0).then(|| {
+            let win = self.clone();
+            glib::timeout_add_seconds_local_once(discovery_timeout as u32, move || {
+                // The source is gone once it fires; make sure stop_scan doesn't remove it again
+                if let Some(scan) = win.scan.borrow_mut().as_mut() {
+                    scan.timeout = None;
+                }
+                win.scan_button.set_active(false);
+            })
+        });
+
+        self.scan.replace(Some(Scan { task, timeout }));
+        self.scan_spinner.set_visible(true);
+        self.scan_spinner.start();
+    }
+
+    fn stop_scan(&self) {
+        let scan = self.scan.borrow_mut().take();
+        if let Some(scan) = scan {
+            scan.task.abort();
+            if let Some(timeout) = scan.timeout {
+                timeout.remove();
+            }
+        }
+        self.scan_spinner.stop();
+        self.scan_spinner.set_visible(false);
+    }
+
+    /// Applies `power_policy` at startup, then shows the resulting state.
+    fn apply_power_policy(&self) {
+        let (policy, last_powered) = {
+            let config = self.config.borrow();
+            (config.power_policy, config.last_powered)
+        };
+        let win = self.clone();
+        glib::spawn_future_local(async move {
+            let service = &win.bluetooth_service;
+            let wanted = match policy {
+                PowerPolicy::Leave => None,
+                PowerPolicy::AlwaysOn => Some(true),
+                PowerPolicy::RestoreLast => last_powered,
+            };
+            let result = match wanted {
+                Some(true) => service.power_on().await,
+                Some(false) => service.power_off().await,
+                None => Ok(()),
+            };
+            if let Err(e) = result {
+                win.show_error(
+                    if wanted == Some(true) { "Could not turn Bluetooth on" } else { "Could not turn Bluetooth off" },
+                    &e,
+                );
+            }
+            win.read_power_state().await;
+        });
+    }
+
+    fn sync_power_state(&self) {
+        let win = self.clone();
+        glib::spawn_future_local(async move {
+            win.read_power_state().await;
+        });
+    }
+
+    /// Shows what the adapter reports, or off if it can't be asked.
+    async fn read_power_state(&self) {
+        let powered = self.bluetooth_service.is_powered().await.unwrap_or_else(|e| {
+            eprintln!("Failed to read power state: {}", e);
+            false
+        });
+        self.show_power_state(powered);
+    }
+
+    fn show_power_state(&self, powered: bool) {
+        self.powered.set(powered);
+        self.update_power_controls();
+    }
+
+    /// Follows rfkill blocks on the Bluetooth radios for the life of the
+    /// application; the window is hidden, never destroyed.
+    fn watch_rfkill(&self) {
+        self.show_rfkill_state(self.bluetooth_service.rfkill_state());
+
+        let mut changes = match self.bluetooth_service.watch_rfkill() {
+            Ok(changes) => changes,
+            Err(e) => {
+                eprintln!("Not watching rfkill: {:#}", e);
+                return;
+            }
+        };
+        let win = self.clone();
+        glib::spawn_future_local(async move {
+            while let Some(state) = changes.next().await {
+                win.show_rfkill_state(state);
+            }
+        });
+    }
+
+    fn show_rfkill_state(&self, state: RfkillState) {
+        let previous = self.rfkill.replace(state);
+        let ready = self.bluetooth_service.state() == ServiceState::Ready;
+        if previous.hard != state.hard {
+            self.toggle_switch.set_sensitive(ready && !state.hard);
+        }
+        self.update_power_controls();
+        // Unblocking may power the adapter back on
+        if ready && previous.is_blocked() && !state.is_blocked() {
+            self.sync_power_state();
+        }
+    }
+
+    /// Syncs the header switch, status and unblock button with the power and rfkill state.
+    fn update_power_controls(&self) {
+        let powered = self.powered.get();
+        let rfkill = self.rfkill.get();
+
+        if self.toggle_switch.is_active() != powered {
+            if let Some(handler) = self.power_handler.get() {
+                self.toggle_switch.block_signal(handler);
+                self.toggle_switch.set_active(powered);
+                self.toggle_switch.unblock_signal(handler);
+            }
+        }
+        self.toggle_switch.set_tooltip_text(rfkill.hard.then_some("Blocked by a hardware switch"));
+        self.unblock_button.set_visible(rfkill.soft && !rfkill.hard);
+
+        // The unavailable state keeps its own status
+        if self.bluetooth_service.state() == ServiceState::Ready {
+            let simulated = self.config.borrow().backend == BackendKind::Simulation;
+            self.status_label.set_markup(&power_markup(powered, rfkill, simulated));
+        }
+    }
+
+    /// Row widgets are created once per visible slot and rebound as the user
+    /// scrolls, so the actions look up the bound device at click time.
+    fn device_row_factory(&self) -> SignalListItemFactory {
+        let factory = SignalListItemFactory::new();
+        let (show_battery, show_address) = {
+            let config = self.config.borrow();
+            (config.show_battery_levels, config.show_device_addresses)
+        };
+        let win = self.clone();
+
+        factory.connect_setup(move |_, item| {
+            let Some(item) = item.downcast_ref::<ListItem>() else { return };
+            let row = DeviceRow::new(show_battery, show_address);
+
+            let w = win.clone();
+            row.connect_toggled(move |addr, state| {
+                let service = w.bluetooth_service.clone();
+                let verb = if state { "connect" } else { "disconnect" };
+                let action = async move {
+                    if state {
+                        service.connect_device(addr).await
+                    } else {
+                        service.disconnect_device(addr).await
+                    }
+                };
+                w.run_device_action(addr, verb, action, move |d| d.connected = state);
+            });
+
+            let w = win.clone();
+            row.connect_pin_toggled(move |addr, pinned| w.set_pinned(addr, pinned));
+
+            let w = win.clone();
+            row.connect_pair_clicked(move |addr| {
+                let service = w.bluetooth_service.clone();
+                let action = async move { service.pair_device(addr).await };
+                w.run_device_action(addr, "pair", action, |d| d.paired = true);
+            });
+
+            let w = win.clone();
+            row.connect_trusted_toggled(move |addr, trusted| {
+                let service = w.bluetooth_service.clone();
+                let verb = if trusted { "trust" } else { "stop trusting" };
+                let action = async move { service.set_trusted(addr, trusted).await };
+                w.run_device_action(addr, verb, action, move |d| d.trusted = trusted);
+            });
+
+            let w = win.clone();
+            row.connect_blocked_toggled(move |addr, blocked| {
+                let w = w.clone();
+                glib::spawn_future_local(async move {
+                    let confirmed = !blocked
+                        || w.confirm(
+                            &format!("Block {}?", w.plain_device_name(addr)),
+                            "It will be disconnected, and its connections refused until you unblock it.",
+                            "Block",
+                        )
+                        .await;
+                    if confirmed {
+                        let service = w.bluetooth_service.clone();
+                        let verb = if blocked { "block" } else { "unblock" };
+                        let action = async move { service.set_blocked(addr, blocked).await };
+                        w.run_device_action(addr, verb, action, move |d| {
+                            d.blocked = blocked;
+                            d.connected &= !blocked;
                         });
                     }
+                });
+            });
+
+            let w = win.clone();
+            row.connect_rename(move |addr| {
+                let w = w.clone();
+                glib::spawn_future_local(async move {
+                    let Some(object) = w.devices.borrow().get(&addr).cloned() else { return };
+                    let device = object.device();
+                    let Some(alias) =
+                        rename_dialog::ask_name(&w.window, &device.name, device.remote_name.as_deref()).await
+                    else {
+                        return;
+                    };
+                    if alias == device.name {
+                        return;
+                    }
+
+                    let service = w.bluetooth_service.clone();
+                    let name = alias.clone();
+                    let action = async move { service.rename_device(addr, name).await };
+                    w.run_device_action(addr, "rename", action, move |d| {
+                        // With no alias and no remote name, the row falls back to the address
+                        d.name = match alias.is_empty() {
+                            true => d.remote_name.clone().unwrap_or_default(),
+                            false => alias,
+                        };
+                    });
+                });
+            });
+
+            let w = win.clone();
+            row.connect_details(move |addr| {
+                let w = w.clone();
+                glib::spawn_future_local(async move {
+                    match w.bluetooth_service.device_details(addr).await {
+                        Ok(details) => details_dialog::show(&w.window, &details),
+                        Err(e) => w.show_error(&format!("Could not read details of <b>{}</b>", w.device_name(addr)), &e),
+                    }
+                });
+            });
+
+            let w = win.clone();
+            row.connect_forget(move |addr| {
+                let w = w.clone();
+                glib::spawn_future_local(async move {
+                    let confirmed = w
+                        .confirm(
+                            &format!("Forget {}?", w.plain_device_name(addr)),
+                            "Its pairing is removed; you will need to pair it again to use it.",
+                            "Forget",
+                        )
+                        .await;
+                    if confirmed {
+                        let service = w.bluetooth_service.clone();
+                        let action = async move { service.remove_device(addr).await };
+                        // The backend reports the removal
+                        w.run_device_action(addr, "forget", action, |_| {});
+                    }
+                });
+            });
+
+            item.set_child(Some(&row.root));
+            item.connect_item_notify(move |item| {
+                row.bind(item.item().and_downcast::<DeviceObject>());
+            });
+        });
+
+        factory
+    }
 
-                    list_box_clone.append(&row_widget.row);
+    /// Runs `action` on `address` with the row's spinner showing. On success
+    /// the row shows `done` straight away instead of waiting for the
+    /// backend's change event; on failure it falls back to the device's last
+    /// known state and the banner says why.
+    fn run_device_action(
+        &self,
+        address: Address,
+        verb: &'static str,
+        action: impl Future<Output = Result<(), BluetoothError>> + 'static,
+        done: impl FnOnce(&mut BluetoothDevice) + 'static,
+    ) {
+        let Some(object) = self.devices.borrow().get(&address).cloned() else { return };
+        object.set_busy(true);
+
+        let win = self.clone();
+        glib::spawn_future_local(async move {
+            match action.await {
+                // Nothing to fix if the device got there on its own
+                Ok(()) | Err(BluetoothError::AlreadyConnected) => {
+                    // Unless it was removed in the meantime
+                    if win.devices.borrow().contains_key(&address) {
+                        let mut device = object.device();
+                        done(&mut device);
+                        win.upsert_device(device);
+                    }
                 }
-                glib::ControlFlow::Break
-            } else {
-                // Keep checking until we receive the data
-                glib::ControlFlow::Continue
+                Err(e) => win.show_error(&format!("Could not {} <b>{}</b>", verb, win.device_name(address)), &e),
+            }
+            object.set_busy(false);
+        });
+    }
+
+    /// Asks before a destructive action; resolves to whether the user
+    /// pressed `accept`.
+    async fn confirm(&self, heading: &str, detail: &str, accept: &str) -> bool {
+        let dialog = AlertDialog::builder()
+            .modal(true)
+            .message(heading)
+            .detail(detail)
+            .buttons(["Cancel", accept])
+            .cancel_button(0)
+            .default_button(0)
+            .build();
+        dialog.choose_future(Some(&self.window)).await == Ok(1)
+    }
+
+    /// Shows a failure in the banner; `what` is markup.
+    fn show_error(&self, what: &str, error: &BluetoothError) {
+        eprintln!("{}", privacy::mask_text(&format!("{}: {:?}", what, error)));
+        self.error_banner.show(what, error);
+    }
+
+    /// Updates the row for `device` in place, or adds one if it is new.
+    fn upsert_device(&self, device: BluetoothDevice) {
+        let existing = self.devices.borrow().get(&device.address).cloned();
+        match existing {
+            Some(object) => {
+                let old = object.device();
+                if old == device {
+                    return;
+                }
+                let reorder = old.name != device.name
+                    || old.connected != device.connected
+                    || old.paired != device.paired;
+                let resection = old.paired != device.paired;
+                object.update(device);
+                if resection {
+                    self.section_sorter.changed(SorterChange::Different);
+                }
+                if reorder {
+                    self.sorter.changed(SorterChange::Different);
+                }
+            }
+            None => {
+                let address = device.address;
+                let pinned = self.config.borrow().pinned_devices.iter().any(|pin| pin.matches(&device));
+                let object = DeviceObject::new(device);
+                object.set_pinned(pinned);
+                self.store.append(&object);
+                self.devices.borrow_mut().insert(address, object);
             }
+        }
+    }
+
+    /// Pins or unpins the device at `address`, saves the config and re-sorts.
+    fn set_pinned(&self, address: Address, pinned: bool) {
+        let Some(object) = self.devices.borrow().get(&address).cloned() else { return };
+        let device = object.device();
+
+        let mut config = self.config.borrow_mut();
+        if pinned {
+            config.pinned_devices.push(PinnedDevice::new(&device));
+        } else {
+            config.pinned_devices.retain(|pin| !pin.matches(&device));
+        }
+        if let Err(e) = config.save() {
+            eprintln!("Failed to save pinned devices: {}", e);
+        }
+        let pins = config.pinned_devices.clone();
+        drop(config);
+
+        // A name pin can cover several devices, so recheck them all.
+        for object in self.devices.borrow().values() {
+            let device = object.device();
+            object.set_pinned(pins.iter().any(|pin| pin.matches(&device)));
+        }
+        self.sorter.changed(SorterChange::Different);
+    }
+
+    fn remove_device(&self, address: Address) {
+        if let Some(object) = self.devices.borrow_mut().remove(&address) {
+            if let Some(position) = self.store.find(&object) {
+                self.store.remove(position);
+            }
+        }
+    }
+
+    /// Reconciles the model with a full device snapshot.
+    fn apply_snapshot(&self, devices: Vec<BluetoothDevice>) {
+        let present: HashSet<Address> = devices.iter().map(|d| d.address).collect();
+        self.devices.borrow_mut().retain(|address, _| present.contains(address));
+        self.store.retain(|item| {
+            item.downcast_ref::<DeviceObject>()
+                .is_some_and(|obj| present.contains(&obj.device().address))
         });
+
+        for device in devices {
+            self.upsert_device(device);
+        }
+    }
+
+    pub fn refresh_devices(&self) {
+        let win = self.clone();
+        glib::spawn_future_local(async move {
+            // Keep the list as it is; the next event or refresh brings it up to date
+            match win.bluetooth_service.get_devices().await {
+                Ok(devices) => win.apply_snapshot(devices),
+                Err(e) => eprintln!("Failed to list devices: {}", e),
+            }
+        });
+    }
+}
+
+/// Section headers for the device list, keyed on the first device of each section.
+fn section_header_factory() -> SignalListItemFactory {
+    let factory = SignalListItemFactory::new();
+
+    factory.connect_setup(|_, header| {
+        let Some(header) = header.downcast_ref::<ListHeader>() else { return };
+        let label = Label::builder()
+            .xalign(0.0)
+            .margin_start(8)
+            .margin_top(6)
+            .margin_bottom(2)
+            .css_classes(vec!["dim-label"])
+            .build();
+        header.set_child(Some(&label));
+    });
+
+    factory.connect_bind(|_, header| {
+        let Some(header) = header.downcast_ref::<ListHeader>() else { return };
+        let Some(label) = header.child().and_downcast::<Label>() else { return };
+        let paired = header
+            .item()
+            .and_downcast::<DeviceObject>()
+            .is_some_and(|obj| obj.device().paired);
+        label.set_label(if paired { "My devices" } else { "Available devices" });
+    });
+
+    factory
+}
+
+/// Whether the application's focused window is a prompt transient for `window`.
+fn has_active_dialog(window: &ApplicationWindow) -> bool {
+    window
+        .application()
+        .and_then(|app| app.active_window())
+        .and_then(|active| active.transient_for())
+        .is_some_and(|parent| &parent == window.upcast_ref::<gtk4::Window>())
+}
+
+fn power_markup(powered: bool, rfkill: RfkillState, simulated: bool) -> String {
+    let state = if rfkill.hard {
+        "<span foreground='red'>Blocked (hardware)</span>"
+    } else if rfkill.soft {
+        "<span foreground='orange'>Blocked (software)</span>"
+    } else if powered {
+        "<span foreground='green'>On</span>"
+    } else {
+        "<span foreground='red'>Off</span>"
+    };
+    if simulated {
+        format!("<b>Bluetooth</b> {} <span foreground='orange'>(Simulation)</span>", state)
+    } else {
+        format!("<b>Bluetooth</b> {}", state)
     }
 }
\ No newline at end of file
@@ -0,0 +1,202 @@
+use dbus::arg::{prop_cast, PropMap};
+use dbus::blocking::{Connection, Proxy};
+use dbus::channel::Channel;
+use dbus::message::MatchRule;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const DEST: &str = "org.bluewidget";
+const PATH: &str = "/org/bluewidget/Manager";
+const INTERFACE: &str = "org.bluewidget.Manager";
+
+/// Connected at the start, and draining its battery so the widget keeps
+/// sending `DeviceChanged`.
+const HEADPHONES: &str = "00:1A:7D:DA:71:01";
+/// Paired but not connected.
+const KEYBOARD: &str = "00:1A:7D:DA:71:02";
+
+/// Kills the process when dropped, so a failed assertion leaves nothing running.
+struct Process(Child);
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// Removes the directory when dropped, whether or not the test passed.
+struct TempDir(PathBuf);
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Signals from the widget, as `(member, details)`: the address and
+/// connected flag for `DeviceChanged`, the new state for `PowerChanged`.
+type Seen = Arc<Mutex<Vec<(String, String)>>>;
+
+/// Starts a private session bus and returns it with its address.
+fn private_bus() -> (Process, String) {
+    let mut daemon = Command::new("dbus-daemon")
+        .args(["--session", "--nofork", "--print-address"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("dbus-daemon runs");
+    let mut address = String::new();
+    BufReader::new(daemon.stdout.take().unwrap()).read_line(&mut address).unwrap();
+    (Process(daemon), address.trim().to_string())
+}
+
+/// A config home selecting the simulation backend with a small scenario.
+fn simulation_config() -> TempDir {
+    let home = TempDir(std::env::temp_dir().join(format!("bluetooth-widget-dbus-test-{}", std::process::id())));
+    let dir = home.0.join("bluetooth-widget");
+    std::fs::create_dir_all(&dir).unwrap();
+    let scenario = serde_json::json!({
+        "powered": true,
+        "devices": [
+            { "address": HEADPHONES, "name": "Headphones", "paired": true, "connected": true,
+              "battery": 100, "battery_drain_ms": 100 },
+            { "address": KEYBOARD, "name": "Keyboard", "paired": true },
+        ],
+    });
+    std::fs::write(home.0.join("scenario.json"), scenario.to_string()).unwrap();
+    // An empty event file reads as no radios, i.e. unblocked
+    std::fs::write(home.0.join("rfkill"), []).unwrap();
+    let config = serde_json::json!({
+        "backend": "simulation",
+        "simulation_scenario": home.0.join("scenario.json"),
+        "rfkill_path": home.0.join("rfkill"),
+        "power_policy": "leave",
+    });
+    std::fs::write(dir.join("config.json"), config.to_string()).unwrap();
+    home
+}
+
+/// Processes incoming messages until `done` holds for the signals seen so far.
+fn wait_for(connection: &Connection, seen: &Seen, what: &str, done: impl Fn(&[(String, String)]) -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !done(&seen.lock().unwrap()) {
+        assert!(Instant::now() < deadline, "timed out waiting for {}; saw {:?}", what, seen.lock().unwrap());
+        connection.process(Duration::from_millis(100)).unwrap();
+    }
+}
+
+fn address(device: &PropMap) -> &str {
+    prop_cast::<String>(device, "address").map(String::as_str).unwrap_or_default()
+}
+
+fn connected(device: &PropMap) -> bool {
+    prop_cast::<bool>(device, "connected").copied().unwrap_or_default()
+}
+
+fn call(proxy: &Proxy<'_, &Connection>, method: &str) {
+    proxy.method_call::<(), _, _, _>(INTERFACE, method, ()).unwrap_or_else(|e| panic!("{} failed: {}", method, e));
+}
+
+#[test]
+#[ignore = "needs a display and dbus-daemon; run with --ignored"]
+fn manager_api_drives_the_simulated_backend() {
+    assert!(
+        std::env::var_os("DISPLAY").is_some() || std::env::var_os("WAYLAND_DISPLAY").is_some(),
+        "GTK needs a display"
+    );
+    let (_bus, bus_address) = private_bus();
+    let config_home = simulation_config();
+    let _widget = Process(
+        Command::new(env!("CARGO_BIN_EXE_bluetooth-widget"))
+            .arg("background")
+            .env("DBUS_SESSION_BUS_ADDRESS", &bus_address)
+            .env("XDG_CONFIG_HOME", &config_home.0)
+            .spawn()
+            .unwrap(),
+    );
+
+    let mut channel = Channel::open_private(&bus_address).unwrap();
+    channel.register().unwrap();
+    let connection = Connection::from(channel);
+    let bus = connection.with_proxy("org.freedesktop.DBus", "/org/freedesktop/DBus", Duration::from_secs(5));
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let (owned,): (bool,) = bus.method_call("org.freedesktop.DBus", "NameHasOwner", (DEST,)).unwrap();
+        if owned {
+            break;
+        }
+        assert!(Instant::now() < deadline, "{} never appeared on the bus", DEST);
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    let seen = Seen::default();
+    let changes = seen.clone();
+    connection
+        .add_match(MatchRule::new_signal(INTERFACE, "DeviceChanged"), move |(device,): (PropMap,), _, _| {
+            let details = format!("{} {}", address(&device), connected(&device));
+            changes.lock().unwrap().push(("DeviceChanged".to_string(), details));
+            true
+        })
+        .unwrap();
+    let changes = seen.clone();
+    connection
+        .add_match(MatchRule::new_signal(INTERFACE, "PowerChanged"), move |(powered,): (bool,), _, _| {
+            changes.lock().unwrap().push(("PowerChanged".to_string(), powered.to_string()));
+            true
+        })
+        .unwrap();
+
+    let proxy = connection.with_proxy(DEST, PATH, Duration::from_secs(10));
+    let (devices,): (Vec<PropMap>,) = proxy.method_call(INTERFACE, "GetDevices", ()).unwrap();
+    let headphones = devices.iter().find(|device| address(device) == HEADPHONES).expect("demo headphones listed");
+    assert!(connected(headphones));
+    assert!(devices.iter().any(|device| address(device) == KEYBOARD && !connected(device)));
+    let (powered,): (bool,) = proxy.method_call(INTERFACE, "GetPowered", ()).unwrap();
+    assert!(powered);
+    // The widget subscribes to backend events once the backend is ready;
+    // the draining battery shows when it has, so the signals below aren't missed
+    wait_for(&connection, &seen, "the first battery update", |seen| {
+        seen.iter().any(|(member, _)| member == "DeviceChanged")
+    });
+
+    for method in ["Show", "Toggle", "Toggle", "Hide"] {
+        call(&proxy, method);
+    }
+
+    proxy.method_call::<(), _, _, _>(INTERFACE, "Disconnect", (HEADPHONES,)).unwrap();
+    let disconnected = format!("{} false", HEADPHONES);
+    wait_for(&connection, &seen, "headphones to disconnect", |seen| {
+        seen.iter().any(|(member, details)| member == "DeviceChanged" && *details == disconnected)
+    });
+
+    proxy.method_call::<(), _, _, _>(INTERFACE, "Connect", (KEYBOARD,)).unwrap();
+    let keyboard_connected = format!("{} true", KEYBOARD);
+    wait_for(&connection, &seen, "keyboard to connect", |seen| {
+        seen.iter().any(|(member, details)| member == "DeviceChanged" && *details == keyboard_connected)
+    });
+
+    proxy.method_call::<(), _, _, _>(INTERFACE, "SetPowered", (false,)).unwrap();
+    wait_for(&connection, &seen, "power off", |seen| {
+        seen.iter().any(|(member, details)| member == "PowerChanged" && details == "false")
+    });
+    let (powered,): (bool,) = proxy.method_call(INTERFACE, "GetPowered", ()).unwrap();
+    assert!(!powered);
+
+    let error = proxy
+        .method_call::<(), _, _, _>(INTERFACE, "Connect", (KEYBOARD,))
+        .expect_err("connecting with the adapter off fails");
+    assert_eq!(error.name(), Some("org.bluewidget.Error.NotReady"));
+
+    proxy.method_call::<(), _, _, _>(INTERFACE, "SetPowered", (true,)).unwrap();
+    wait_for(&connection, &seen, "power on", |seen| {
+        seen.iter().any(|(member, details)| member == "PowerChanged" && details == "true")
+    });
+
+    let error = proxy
+        .method_call::<(), _, _, _>(INTERFACE, "Connect", ("not an address",))
+        .expect_err("a malformed address is rejected");
+    assert_eq!(error.name(), Some("org.freedesktop.DBus.Error.InvalidArgs"));
+}